@@ -1,62 +1,537 @@
-use std::{io::BufRead, time::Instant};
+use std::{
+    cmp::Reverse,
+    io::{BufRead, BufWriter, Write},
+    str::FromStr,
+    time::Instant,
+};
+
+use aho_corasick::AhoCorasick;
 
 const NUMS: &[&[u8]] = &[
     b"one", b"two", b"three", b"four", b"five", b"six", b"seven", b"eight", b"nine",
 ];
 
-fn input() -> Vec<String> {
-    std::io::stdin()
-        .lock()
-        .lines()
-        .map(|line| line.unwrap())
-        .collect()
+/// A token found in a line, `start..end` are byte offsets
+#[derive(Debug, Clone, Copy)]
+struct TokenMatch {
+    value: usize,
+    start: usize,
+    end: usize,
 }
 
-fn main() {
-    let time = Instant::now();
-    let input = input();
-    part1(&input);
-    part2(&input);
-    println!("Time elapsed is {:?}", time.elapsed())
+/// The tokens that stand for a digit, matched with an Aho-Corasick automaton so that every token
+/// is found in a single pass, overlapping ones included
+/// https://en.wikipedia.org/wiki/Aho%E2%80%93Corasick_algorithm
+struct DigitVocabulary {
+    /// Digit value of every token, by pattern id
+    values: Vec<usize>,
+    automaton: AhoCorasick,
 }
 
-fn part1(input: &[String]) {
-    let result: i32 = input
-        .iter()
-        .map(|line| {
-            let bytes = line.as_bytes();
-            let first_digit = bytes.iter().find(|&&b| b.is_ascii_digit()).unwrap();
-            let last_digit = bytes.iter().rev().find(|&&b| b.is_ascii_digit()).unwrap();
+impl DigitVocabulary {
+    /// Build the automaton, the builder picks the fastest kind that fits the vocabulary
+    fn new(tokens: Vec<(Vec<u8>, usize)>) -> Result<Self, String> {
+        let (patterns, values): (Vec<Vec<u8>>, Vec<usize>) = tokens.into_iter().unzip();
+        let automaton = AhoCorasick::new(patterns).map_err(|e| e.to_string())?;
+        Ok(DigitVocabulary { values, automaton })
+    }
 
-            ((first_digit - b'0') * 10 + (last_digit - b'0')) as i32
-        })
-        .sum();
-    println!("Part 1 answer: {:?}", result);
+    fn digit_tokens() -> impl Iterator<Item = (Vec<u8>, usize)> {
+        (0..10).map(|digit| (vec![b'0' + digit as u8], digit))
+    }
+
+    /// Only the digits themselves
+    fn digits() -> Self {
+        Self::new(Self::digit_tokens().collect()).expect("The digits always build")
+    }
+
+    /// The digits and the English words `one` to `nine`
+    fn english() -> Self {
+        let words = NUMS
+            .iter()
+            .enumerate()
+            .map(|(num, name)| (name.to_vec(), num + 1));
+        Self::new(Self::digit_tokens().chain(words).collect())
+            .expect("The English words always build")
+    }
+
+    /// The digits and the tokens listed in a file, one `<token> <digit>` per line. Empty lines and
+    /// lines starting with `#` are ignored, every token may only be listed once.
+    fn from_file(path: &str) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut tokens: Vec<(Vec<u8>, usize)> = Self::digit_tokens().collect();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let token = line
+                .split_once(char::is_whitespace)
+                .and_then(|(token, value)| Some((token, value.trim().parse().ok()?)))
+                .filter(|&(_, value)| value < 10);
+            let Some((token, value)) = token else {
+                return Err(format!("{}:{}: expected '<token> <digit>'", path, i + 1));
+            };
+            if tokens.iter().any(|(known, _)| known == token.as_bytes()) {
+                return Err(format!("{}:{}: duplicate token '{}'", path, i + 1, token));
+            }
+            tokens.push((token.as_bytes().to_vec(), value));
+        }
+        Self::new(tokens).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// The first and last token of a line. Overlapping tokens all count, so `twone` starts with 2
+    /// and ends with 1. When tokens start at the same offset the longest one wins.
+    fn first_last(&self, line: &[u8]) -> Option<(TokenMatch, TokenMatch)> {
+        self.automaton
+            .find_overlapping_iter(line)
+            .map(|m| TokenMatch {
+                value: self.values[m.pattern().as_usize()],
+                start: m.start(),
+                end: m.end(),
+            })
+            .fold(None, |found, m| match found {
+                None => Some((m, m)),
+                Some((first, last)) => Some((
+                    if (m.start, Reverse(m.end)) < (first.start, Reverse(first.end)) {
+                        m
+                    } else {
+                        first
+                    },
+                    if (m.start, m.end) > (last.start, last.end) {
+                        m
+                    } else {
+                        last
+                    },
+                )),
+            })
+    }
+}
+
+/// What to do with a line without any digit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MissingDigitPolicy {
+    /// Stop with the line number
+    Error,
+    /// Leave the line out of the sum
+    Skip,
+    /// Count the line as 0
+    Zero,
+}
+
+impl FromStr for MissingDigitPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(MissingDigitPolicy::Error),
+            "skip" => Ok(MissingDigitPolicy::Skip),
+            "zero" => Ok(MissingDigitPolicy::Zero),
+            _ => Err(format!("Unknown policy '{}'", s)),
+        }
+    }
+}
+
+/// The calibration sum and the (1-based) numbers of the lines without a digit
+#[derive(Default)]
+struct Calibration {
+    sum: usize,
+    problem_lines: Vec<usize>,
+}
+
+impl Calibration {
+    fn add(
+        &mut self,
+        line_number: usize,
+        line: &[u8],
+        vocabulary: &DigitVocabulary,
+        policy: MissingDigitPolicy,
+    ) -> Result<(), String> {
+        match vocabulary.first_last(line) {
+            Some((first, last)) => self.sum += first.value * 10 + last.value,
+            None if policy == MissingDigitPolicy::Error => {
+                return Err(format!(
+                    "Line {} has no digit: {:?}",
+                    line_number,
+                    String::from_utf8_lossy(line)
+                ));
+            }
+            None => self.problem_lines.push(line_number),
+        }
+        Ok(())
+    }
+}
+
+/// Call `f` with the number and content of every line, reading into one reused buffer so memory
+/// stays constant whatever the input size. Returns the number of bytes read.
+fn for_each_line(
+    mut reader: impl BufRead,
+    mut f: impl FnMut(usize, &[u8]) -> Result<(), String>,
+) -> Result<usize, String> {
+    let mut bytes = 0;
+    let mut buf = Vec::new();
+    for line_number in 1.. {
+        buf.clear();
+        let read = reader
+            .read_until(b'\n', &mut buf)
+            .map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        bytes += read;
+
+        let line = buf.strip_suffix(b"\n").unwrap_or(&buf);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        f(line_number, line)?;
+    }
+    Ok(bytes)
+}
+
+/// Both parts in a single pass over the input, returns the calibrations and the bytes read
+fn process(
+    reader: impl BufRead,
+    vocabulary: &DigitVocabulary,
+    policy: MissingDigitPolicy,
+) -> Result<(Calibration, Calibration, usize), String> {
+    let digits = DigitVocabulary::digits();
+    let mut part1 = Calibration::default();
+    let mut part2 = Calibration::default();
+    let bytes = for_each_line(reader, |line_number, line| {
+        part1
+            .add(line_number, line, &digits, policy)
+            .map_err(|err| format!("Part 1: {}", err))?;
+        part2
+            .add(line_number, line, vocabulary, policy)
+            .map_err(|err| format!("Part 2: {}", err))
+    })?;
+    Ok((part1, part2, bytes))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TraceFormat {
+    Text,
+    Csv,
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(TraceFormat::Text),
+            "csv" => Ok(TraceFormat::Csv),
+            _ => Err(format!("Unknown trace format '{}'", s)),
+        }
+    }
+}
+
+/// Quote a CSV field when it contains a separator, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Write the first and last token matched on every line, where they matched and the resulting
+/// calibration value
+fn trace(
+    reader: impl BufRead,
+    vocabulary: &DigitVocabulary,
+    format: TraceFormat,
+    mut out: impl Write,
+) -> Result<(), String> {
+    if format == TraceFormat::Csv {
+        writeln!(
+            out,
+            "line,first_token,first_offset,first_kind,last_token,last_offset,last_kind,value"
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    for_each_line(reader, |line_number, line| {
+        // The matched text and whether it is a digit or a word
+        let describe = |m: &TokenMatch| {
+            let token = String::from_utf8_lossy(&line[m.start..m.end]).into_owned();
+            let kind = match token.len() == 1 && token.as_bytes()[0].is_ascii_digit() {
+                true => "digit",
+                false => "word",
+            };
+            (token, kind)
+        };
+        let written = match (vocabulary.first_last(line), format) {
+            (None, TraceFormat::Text) => writeln!(out, "line {}: no digit", line_number),
+            (None, TraceFormat::Csv) => writeln!(out, "{},,,,,,,", line_number),
+            (Some((first, last)), format) => {
+                let (first_token, first_kind) = describe(&first);
+                let (last_token, last_kind) = describe(&last);
+                let value = first.value * 10 + last.value;
+                match format {
+                    TraceFormat::Text => writeln!(
+                        out,
+                        "line {}: first {:?} at {} ({}), last {:?} at {} ({}) -> {}",
+                        line_number,
+                        first_token,
+                        first.start,
+                        first_kind,
+                        last_token,
+                        last.start,
+                        last_kind,
+                        value
+                    ),
+                    TraceFormat::Csv => writeln!(
+                        out,
+                        "{},{},{},{},{},{},{},{}",
+                        line_number,
+                        csv_field(&first_token),
+                        first.start,
+                        first_kind,
+                        csv_field(&last_token),
+                        last.start,
+                        last_kind,
+                        value
+                    ),
+                }
+            }
+        };
+        written.map_err(|e| e.to_string())
+    })?;
+    Ok(())
 }
 
-fn part2(input: &[String]) {
-    let result: usize = input
+fn main() {
+    let time = Instant::now();
+
+    // Use the tokens from a file for part 2: --vocabulary <file>
+    let args: Vec<String> = std::env::args().collect();
+    let vocabulary = match args.iter().position(|arg| arg == "--vocabulary") {
+        Some(i) => {
+            let path = args.get(i + 1).expect("--vocabulary expects a file");
+            DigitVocabulary::from_file(path).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            })
+        }
+        None => DigitVocabulary::english(),
+    };
+
+    // Handle lines without a digit: --missing <error|skip|zero>
+    let policy = args
         .iter()
-        .map(|line| {
-            let bytes = line.as_bytes();
-            let first_digit = (0..bytes.len()).find_map(|i| find_num(bytes, i)).unwrap();
-            let last_digit = (0..bytes.len())
-                .rev()
-                .find_map(|i| find_num(bytes, i))
-                .unwrap();
-            first_digit * 10 + last_digit
+        .position(|arg| arg == "--missing")
+        .map(|i| {
+            args.get(i + 1)
+                .expect("--missing expects a policy")
+                .parse()
+                .unwrap()
         })
-        .sum();
-    println!("Part 2 answer: {:?}", result);
+        .unwrap_or(MissingDigitPolicy::Error);
+
+    // Show which tokens produced the part 2 value of every line: --trace [text|csv]
+    if let Some(i) = args.iter().position(|arg| arg == "--trace") {
+        let format = args
+            .get(i + 1)
+            .filter(|format| !format.starts_with("--"))
+            .map_or(TraceFormat::Text, |format| format.parse().unwrap());
+        let out = BufWriter::new(std::io::stdout().lock());
+        if let Err(err) = trace(std::io::stdin().lock(), &vocabulary, format, out) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let (calibration1, calibration2, bytes) = process(std::io::stdin().lock(), &vocabulary, policy)
+        .unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        });
+    part1(&calibration1, policy);
+    part2(&calibration2, policy);
+
+    let elapsed = time.elapsed();
+    println!("Time elapsed is {:?}", elapsed);
+    println!(
+        "Throughput is {:.1} MB/s",
+        bytes as f64 / 1_000_000.0 / elapsed.as_secs_f64()
+    );
 }
 
-fn find_num(line: &[u8], i: usize) -> Option<usize> {
-    line[i]
-        .is_ascii_digit()
-        .then_some((line[i] - b'0') as usize)
-        .or(NUMS
-            .iter()
-            .enumerate()
-            .find(|(_, name)| line[i..].starts_with(name))
-            .map(|(num, _)| num + 1))
+/// Print every line without a digit after the answer
+fn report(calibration: &Calibration, policy: MissingDigitPolicy) {
+    if !calibration.problem_lines.is_empty() {
+        let action = match policy {
+            MissingDigitPolicy::Zero => "counted as 0",
+            _ => "skipped",
+        };
+        println!(
+            "  {} line(s) without a digit {}: {:?}",
+            calibration.problem_lines.len(),
+            action,
+            calibration.problem_lines
+        );
+    }
+}
+
+fn part1(calibration: &Calibration, policy: MissingDigitPolicy) {
+    println!("Part 1 answer: {:?}", calibration.sum);
+    report(calibration, policy);
+}
+
+fn part2(calibration: &Calibration, policy: MissingDigitPolicy) {
+    println!("Part 2 answer: {:?}", calibration.sum);
+    report(calibration, policy);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The values of the first and last token of `line`
+    fn values(vocabulary: &DigitVocabulary, line: &str) -> Option<(usize, usize)> {
+        vocabulary
+            .first_last(line.as_bytes())
+            .map(|(first, last)| (first.value, last.value))
+    }
+
+    /// Path of a vocabulary file checked in next to the manifest
+    fn vocabulary_file(name: &str) -> String {
+        format!("{}/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    #[test]
+    fn overlapping_words() {
+        let english = DigitVocabulary::english();
+        assert_eq!(Some((2, 1)), values(&english, "twone"));
+        assert_eq!(Some((8, 2)), values(&english, "eightwo"));
+        assert_eq!(Some((1, 4)), values(&english, "zoneight234"));
+        assert_eq!(Some((7, 6)), values(&english, "7pqrstsixteen"));
+        assert_eq!(None, values(&english, "zero"));
+
+        let digits = DigitVocabulary::digits();
+        assert_eq!(None, values(&digits, "twone"));
+        assert_eq!(Some((0, 3)), values(&digits, "a0b3c"));
+    }
+
+    #[test]
+    fn longest_token_wins_at_the_same_offset() {
+        let vocabulary =
+            DigitVocabulary::new(vec![(b"on".to_vec(), 5), (b"one".to_vec(), 1)]).unwrap();
+        assert_eq!(Some((1, 1)), values(&vocabulary, "xonex"));
+        assert_eq!(Some((5, 5)), values(&vocabulary, "xonx"));
+    }
+
+    #[test]
+    fn custom_vocabulary() {
+        let vocabulary = DigitVocabulary::from_file(&vocabulary_file("vocabulary_de.txt")).unwrap();
+        assert_eq!(Some((0, 3)), values(&vocabulary, "nulldrei"));
+        assert_eq!(Some((3, 0)), values(&vocabulary, "dreinull"));
+        assert_eq!(Some((7, 5)), values(&vocabulary, "7fünf"));
+        assert_eq!(None, values(&vocabulary, "one"));
+
+        let vocabulary = DigitVocabulary::from_file(&vocabulary_file("vocabulary_en.txt")).unwrap();
+        assert_eq!(Some((0, 2)), values(&vocabulary, "zerotwo"));
+        assert_eq!(Some((2, 0)), values(&vocabulary, "twozero"));
+        assert_eq!(Some((7, 0)), values(&vocabulary, "7zero"));
+        assert_eq!(Some((8, 2)), values(&vocabulary, "eightwo"));
+    }
+
+    #[test]
+    fn malformed_vocabulary_file() {
+        let path = vocabulary_file("vocabulary_malformed.txt");
+        assert_eq!(
+            Some(format!("{}:3: expected '<token> <digit>'", path)),
+            DigitVocabulary::from_file(&path).err()
+        );
+        let path = vocabulary_file("vocabulary_duplicate.txt");
+        assert_eq!(
+            Some(format!("{}:3: duplicate token '1'", path)),
+            DigitVocabulary::from_file(&path).err()
+        );
+        assert!(DigitVocabulary::from_file("/nonexistent/vocabulary.txt").is_err());
+    }
+
+    const MISSING_DIGIT: &str = "1abc2\nnothing\ntwo3\n";
+
+    fn process_with(
+        policy: MissingDigitPolicy,
+    ) -> Result<(Calibration, Calibration, usize), String> {
+        process(
+            MISSING_DIGIT.as_bytes(),
+            &DigitVocabulary::english(),
+            policy,
+        )
+    }
+
+    #[test]
+    fn missing_digit_is_an_error() {
+        assert_eq!(
+            Some("Part 1: Line 2 has no digit: \"nothing\"".to_string()),
+            process_with(MissingDigitPolicy::Error).err()
+        );
+    }
+
+    #[test]
+    fn missing_digit_is_skipped_or_zero() {
+        for policy in [MissingDigitPolicy::Skip, MissingDigitPolicy::Zero] {
+            let (part1, part2, bytes) = process_with(policy).unwrap();
+            assert_eq!(45, part1.sum, "{:?}", policy);
+            assert_eq!(vec![2], part1.problem_lines, "{:?}", policy);
+            assert_eq!(35, part2.sum, "{:?}", policy);
+            assert_eq!(vec![2], part2.problem_lines, "{:?}", policy);
+            assert_eq!(MISSING_DIGIT.len(), bytes);
+        }
+    }
+
+    #[test]
+    fn words_only_lines_are_missing_digits_in_part_1() {
+        let (part1, part2, _) = process(
+            "xone\n7\n".as_bytes(),
+            &DigitVocabulary::english(),
+            MissingDigitPolicy::Skip,
+        )
+        .unwrap();
+        assert_eq!((77, vec![1]), (part1.sum, part1.problem_lines));
+        assert_eq!((88, vec![]), (part2.sum, part2.problem_lines));
+    }
+
+    fn trace_to_string(input: &str, vocabulary: &DigitVocabulary, format: TraceFormat) -> String {
+        let mut out = Vec::new();
+        trace(input.as_bytes(), vocabulary, format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn text_trace() {
+        let trace = trace_to_string(
+            "xtwone3four\n7\nnothing\n",
+            &DigitVocabulary::english(),
+            TraceFormat::Text,
+        );
+        assert_eq!(
+            "line 1: first \"two\" at 1 (word), last \"four\" at 7 (word) -> 24\n\
+             line 2: first \"7\" at 0 (digit), last \"7\" at 0 (digit) -> 77\n\
+             line 3: no digit\n",
+            trace
+        );
+    }
+
+    #[test]
+    fn csv_trace_quotes_fields() {
+        let tokens = DigitVocabulary::digit_tokens()
+            .chain([(b"a,b".to_vec(), 4), (b"\"q".to_vec(), 5)])
+            .collect();
+        let trace = trace_to_string(
+            "x\"q1a,b\nnothing\n",
+            &DigitVocabulary::new(tokens).unwrap(),
+            TraceFormat::Csv,
+        );
+        assert_eq!(
+            "line,first_token,first_offset,first_kind,last_token,last_offset,last_kind,value\n\
+             1,\"\"\"q\",1,word,\"a,b\",4,word,54\n\
+             2,,,,,,,\n",
+            trace
+        );
+    }
 }
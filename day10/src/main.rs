@@ -1,4 +1,12 @@
-use std::{collections::HashMap, io::BufRead, time::Instant};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    io::BufRead,
+    str::FromStr,
+    time::Instant,
+};
+
+use grid::{Grid, Point};
 
 #[derive(Debug, Eq, PartialEq, PartialOrd, Ord, Hash, Clone, Copy)]
 enum Direction {
@@ -8,6 +16,34 @@ enum Direction {
     Right,
 }
 
+impl Direction {
+    const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+
+    /// Row and column offset of a single step
+    fn delta(self) -> (isize, isize) {
+        match self {
+            Direction::Up => (-1, 0),
+            Direction::Down => (1, 0),
+            Direction::Left => (0, -1),
+            Direction::Right => (0, 1),
+        }
+    }
+
+    fn opposite(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+}
+
 fn build_direction_map() -> HashMap<(Direction, char), Direction> {
     use Direction::*;
     [
@@ -28,187 +64,720 @@ fn build_direction_map() -> HashMap<(Direction, char), Direction> {
     .collect()
 }
 
-fn next_point(point: (usize, usize), direction: Direction) -> (usize, usize) {
-    match direction {
-        Direction::Up => (point.0 - 1, point.1),
-        Direction::Down => (point.0 + 1, point.1),
-        Direction::Left => (point.0, point.1 - 1),
-        Direction::Right => (point.0, point.1 + 1),
+/// The pipe connecting the two given directions
+fn pipe_between(a: Direction, b: Direction) -> char {
+    use Direction::*;
+    match (a.min(b), a.max(b)) {
+        (Up, Down) => '|',
+        (Left, Right) => '-',
+        (Up, Left) => 'J',
+        (Up, Right) => 'L',
+        (Down, Left) => '7',
+        (Down, Right) => 'F',
+        _ => unreachable!("A pipe connects two different directions"),
     }
 }
 
-fn find_loop_length(
-    lines: &[String],
-    mut point: (usize, usize),
+/// Follow the pipes from `start` in `direction`, returns the visited tiles (starting with `start`)
+/// and the direction we arrived back at `start` from, if the pipes lead back to it
+fn walk_loop(
+    grid: &Grid<char>,
+    start: Point,
     mut direction: Direction,
     map: &HashMap<(Direction, char), Direction>,
-) -> Option<usize> {
-    let mut len = 0;
+) -> Option<(Vec<Point>, Direction)> {
+    let mut path = vec![start];
+    let mut point = start;
     loop {
-        len += 1;
-        point = next_point(point, direction);
-        let y = point.0;
-        let x = point.1;
-        if let Some(line) = lines.get(y) {
-            if let Some(&new_direction) = map.get(&(direction, line.chars().nth(x)?)) {
-                direction = new_direction;
-            } else if line.chars().nth(x)? == 'S' {
-                return Some(len);
-            } else {
-                return None;
-            }
+        point = grid.offset(point, direction.delta())?;
+        let &tile = grid.get(point)?;
+        if let Some(&new_direction) = map.get(&(direction, tile)) {
+            path.push(point);
+            direction = new_direction;
+        } else if tile == 'S' {
+            return Some((path, direction));
         } else {
             return None;
         }
     }
 }
 
-/// Calculate the length at each step for the first loop completion
-fn calculate_lengths(
-    lines: &[String],
-    start: (usize, usize),
-    map: &HashMap<(Direction, char), Direction>,
-) -> HashMap<(usize, usize), usize> {
-    let base = [
-        Direction::Up,
-        Direction::Down,
-        Direction::Left,
-        Direction::Right,
-    ];
+/// The main loop of the maze
+#[derive(Debug)]
+struct PipeLoop {
+    /// Loop tiles in walking order, starting at `S`
+    path: Vec<Point>,
+    /// Index of every loop tile in `path`
+    index: HashMap<Point, usize>,
+    /// The pipe hidden under `S`
+    start_tile: char,
+}
 
-    let mut length_store = HashMap::new();
+impl PipeLoop {
+    fn find(grid: &Grid<char>) -> Option<PipeLoop> {
+        let start = grid.position(|&c| c == 'S')?;
+        let map = build_direction_map();
 
-    'base: for dir in base {
-        let mut point = start;
-        let mut len = 0;
-        let mut dirx = dir;
+        // We check all four directions, we know that only starting two directions will complete
+        // the same loop
+        let (path, start_direction, end_direction) =
+            Direction::ALL.into_iter().find_map(|direction| {
+                walk_loop(grid, start, direction, &map)
+                    .map(|(path, end_direction)| (path, direction, end_direction))
+            })?;
+        let index = path.iter().enumerate().map(|(i, &p)| (p, i)).collect();
 
-        loop {
-            len += 1;
-            point = next_point(point, dirx);
-            let y = point.0;
-            let x = point.1;
-
-            if let Some(line) = lines.get(y) {
-                if let Some(&new_direction) = map.get(&(dirx, line.chars().nth(x).unwrap())) {
-                    dirx = new_direction;
-                    length_store.insert(point, len);
-                }
-                if line.chars().nth(x).unwrap_or('\0') == 'S' {
-                    length_store.insert(point, len);
-                    break 'base;
-                }
-            } else {
-                break;
-            }
+        Some(PipeLoop {
+            path,
+            index,
+            start_tile: pipe_between(start_direction, end_direction.opposite()),
+        })
+    }
+
+    fn start(&self) -> Point {
+        self.path[0]
+    }
+
+    fn len(&self) -> usize {
+        self.path.len()
+    }
+
+    fn contains(&self, point: Point) -> bool {
+        self.index.contains_key(&point)
+    }
+
+    /// The tile of `point`, with `S` replaced by the pipe it stands for
+    fn tile(&self, grid: &Grid<char>, point: Point) -> char {
+        if point == self.start() {
+            self.start_tile
+        } else {
+            grid[point]
         }
     }
 
-    length_store
+    /// Steps from `S` to `point` along the loop, in walking order and in the opposite direction
+    fn distances(&self, point: Point) -> Option<(usize, usize)> {
+        let &i = self.index.get(&point)?;
+        Some((i, (self.len() - i) % self.len()))
+    }
+
+    /// Steps to the point of the loop farthest from `S`
+    fn farthest_distance(&self) -> usize {
+        self.len() / 2
+    }
 }
 
-fn count_points_inside(
-    lines: &[String],
-    lengths: &HashMap<(usize, usize), usize>,
-    start: (usize, usize),
-) -> usize {
-    let mut total_count = 0;
-    for (row, line) in lines.iter().enumerate() {
+fn find_points_inside(grid: &Grid<char>, pipe_loop: &PipeLoop) -> Vec<Point> {
+    let len = pipe_loop.len();
+    let mut inside = vec![];
+    for row in 0..grid.height() {
         let mut scan = false;
-        let mut count = 0;
 
-        for (col, _) in line.chars().enumerate() {
+        for col in 0..grid.width() {
             // If scan is true and current tile is not part of the loop, this must mean this tile
             // is contained within the loop grid
-            if scan && lengths.get(&(row, col)).is_none() {
-                count += 1;
+            if scan && !pipe_loop.contains((row, col)) {
+                inside.push((row, col));
             }
 
             // Check if the current point and the point below it are on the loop path
-            if let (Some(&a), Some(&b)) = (lengths.get(&(row, col)), lengths.get(&(row + 1, col))) {
+            if let (Some((a, _)), Some((b, _))) = (
+                pipe_loop.distances((row, col)),
+                pipe_loop.distances((row + 1, col)),
+            ) {
                 // Calculate relative position on the loop using modulo arithmetic to handle the
                 // cyclic nature
-                let loop_crossing_check: isize = (((a as isize - b as isize)
-                    % lengths[&start] as isize)
-                    + lengths[&start] as isize)
-                    % lengths[&start] as isize;
+                let loop_crossing_check: isize =
+                    (((a as isize - b as isize) % len as isize) + len as isize) % len as isize;
                 // If loop_crossing_check is 1 or loop length - 1, the loop boundary has been
                 // crossed
-                if [1, lengths[&start] - 1].contains(&(loop_crossing_check as usize)) {
+                if [1, len - 1].contains(&(loop_crossing_check as usize)) {
                     scan = !scan;
                 }
             }
         }
-
-        total_count += count;
     }
 
-    total_count
+    inside
 }
 
-fn input() -> Vec<String> {
-    std::io::stdin()
-        .lock()
-        .lines()
-        .map(|line| line.unwrap())
+/// Count the tiles enclosed by the loop from its vertices, the shoelace formula gives the area of
+/// the polygon through the tile centers and Pick's theorem turns that into the interior tile count
+/// https://en.wikipedia.org/wiki/Shoelace_formula
+/// https://en.wikipedia.org/wiki/Pick%27s_theorem
+fn count_points_inside_shoelace(pipe_loop: &PipeLoop) -> usize {
+    let path = &pipe_loop.path;
+    let double_area = path
+        .iter()
+        .zip(path.iter().cycle().skip(1))
+        .map(|(&(y1, x1), &(y2, x2))| x1 as isize * y2 as isize - x2 as isize * y1 as isize)
+        .sum::<isize>()
+        .unsigned_abs();
+
+    // A = i + b/2 - 1, every loop tile is a boundary point
+    (double_area + 2 - pipe_loop.len()) / 2
+}
+
+/// Find the tiles enclosed by the loop by drawing it on a grid scaled up by `scale`, so that the
+/// gaps between adjacent pipes become walkable, and flood filling the outside from the border
+fn find_points_inside_flood(grid: &Grid<char>, pipe_loop: &PipeLoop, scale: usize) -> Vec<Point> {
+    assert!(
+        scale >= 2,
+        "The grid must be scaled up to squeeze between pipes"
+    );
+
+    // Tile centers in the scaled grid, with a one cell border so the outside is always connected
+    let center = |(row, col): Point| (row * scale + scale / 2 + 1, col * scale + scale / 2 + 1);
+    let mut walls = Grid::new(grid.width() * scale + 2, grid.height() * scale + 2, false);
+    for (&a, &b) in pipe_loop
+        .path
+        .iter()
+        .zip(pipe_loop.path.iter().cycle().skip(1))
+    {
+        let (a, b) = (center(a), center(b));
+        for row in a.0.min(b.0)..=a.0.max(b.0) {
+            for col in a.1.min(b.1)..=a.1.max(b.1) {
+                walls[(row, col)] = true;
+            }
+        }
+    }
+
+    let mut outside = walls.map(|_| false);
+    let mut queue = VecDeque::from([(0, 0)]);
+    outside[(0, 0)] = true;
+    while let Some(point) = queue.pop_front() {
+        for next in walls.neighbors4(point) {
+            if !walls[next] && !outside[next] {
+                outside[next] = true;
+                queue.push_back(next);
+            }
+        }
+    }
+
+    grid.points()
+        .filter(|&point| !pipe_loop.contains(point) && !outside[center(point)])
         .collect()
 }
 
-fn solve(input: &[String]) -> usize {
-    // Find the starting position in the grid
-    let start = input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Strategy {
+    Scanline,
+    Shoelace,
+    /// Flood fill on the grid scaled up by the given factor
+    Flood(usize),
+}
+
+impl Strategy {
+    const ALL: [Strategy; 4] = [
+        Strategy::Scanline,
+        Strategy::Shoelace,
+        Strategy::Flood(2),
+        Strategy::Flood(3),
+    ];
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scanline" => Ok(Strategy::Scanline),
+            "shoelace" => Ok(Strategy::Shoelace),
+            "flood" | "flood2" => Ok(Strategy::Flood(2)),
+            "flood3" => Ok(Strategy::Flood(3)),
+            _ => Err(format!("Unknown strategy '{}'", s)),
+        }
+    }
+}
+
+fn enclosed_tiles(grid: &Grid<char>, pipe_loop: &PipeLoop, strategy: Strategy) -> usize {
+    match strategy {
+        Strategy::Scanline => find_points_inside(grid, pipe_loop).len(),
+        Strategy::Shoelace => count_points_inside_shoelace(pipe_loop),
+        Strategy::Flood(scale) => find_points_inside_flood(grid, pipe_loop, scale).len(),
+    }
+}
+
+/// Draw the maze with the loop in box-drawing characters and every other tile marked `I` (inside)
+/// or `O` (outside). With `color` the inside is shaded and junk pipes are dimmed instead of replaced.
+fn render(grid: &Grid<char>, pipe_loop: &PipeLoop, color: bool) -> String {
+    const RESET: &str = "\x1b[0m";
+    const DIM: &str = "\x1b[2m";
+    const LOOP: &str = "\x1b[1;33m";
+    const INSIDE: &str = "\x1b[42;30m";
+
+    let inside: HashSet<Point> = find_points_inside(grid, pipe_loop).into_iter().collect();
+    let mut out = String::new();
+    for row in 0..grid.height() {
+        for col in 0..grid.width() {
+            let point = (row, col);
+            let tile = if pipe_loop.contains(point) {
+                let pipe = match pipe_loop.tile(grid, point) {
+                    '|' => '│',
+                    '-' => '─',
+                    'L' => '└',
+                    'J' => '┘',
+                    '7' => '┐',
+                    'F' => '┌',
+                    c => c,
+                };
+                match color {
+                    true => format!("{}{}{}", LOOP, pipe, RESET),
+                    false => pipe.to_string(),
+                }
+            } else {
+                let is_inside = inside.contains(&point);
+                match (color, grid[point]) {
+                    (false, _) => (if is_inside { 'I' } else { 'O' }).to_string(),
+                    (true, '.') if is_inside => format!("{}I{}", INSIDE, RESET),
+                    (true, '.') => format!("{}O{}", DIM, RESET),
+                    (true, junk) if is_inside => format!("{}{}{}{}", INSIDE, DIM, junk, RESET),
+                    (true, junk) => format!("{}{}{}", DIM, junk, RESET),
+                }
+            };
+            out.push_str(&tile);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// A problem in the maze, with the coordinates where it occurs
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Problem {
+    RaggedRow {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    MissingStart,
+    DuplicateStart(Point),
+    StartConnections {
+        start: Point,
+        count: usize,
+    },
+    OffGrid {
+        at: Point,
+        tile: char,
+        on_loop: bool,
+    },
+    DeadEnd {
+        at: Point,
+        direction: Direction,
+    },
+}
+
+impl Problem {
+    /// Junk pipes pointing off the grid are harmless, everything else keeps the loop from closing
+    fn is_fatal(&self) -> bool {
+        !matches!(self, Problem::OffGrid { on_loop: false, .. })
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::RaggedRow {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Row {} has {} columns, expected {}",
+                row, found, expected
+            ),
+            Problem::MissingStart => write!(f, "No start tile 'S' found"),
+            Problem::DuplicateStart(at) => write!(f, "Duplicate start tile 'S' at {:?}", at),
+            Problem::StartConnections { start, count } => write!(
+                f,
+                "Start {:?} connects to {} pipes, expected 2",
+                start, count
+            ),
+            Problem::OffGrid { at, tile, on_loop } => write!(
+                f,
+                "{} '{}' at {:?} points off the grid",
+                if *on_loop { "Loop pipe" } else { "Pipe" },
+                tile,
+                at
+            ),
+            Problem::DeadEnd { at, direction } => write!(
+                f,
+                "Loop dead-ends at {:?}, the pipe leads {:?} into a tile that does not connect back",
+                at, direction
+            ),
+        }
+    }
+}
+
+/// Check the maze for everything that would keep the loop from being found
+fn validate(lines: &[String]) -> Vec<Problem> {
+    // Rows are numbered by their line in the input, blank lines included, like `GridError`
+    let rows: Vec<(usize, &String)> = lines
         .iter()
         .enumerate()
-        .find_map(|(i, line)| line.chars().position(|c| c == 'S').map(|j| (i, j)));
+        .filter(|(_, line)| !line.is_empty())
+        .collect();
+    let expected = rows.first().map_or(0, |(_, row)| row.chars().count());
+    let mut problems: Vec<Problem> = rows
+        .iter()
+        .map(|&(row, line)| (row, line.chars().count()))
+        .filter(|&(_, found)| found != expected)
+        .map(|(row, found)| Problem::RaggedRow {
+            row,
+            expected,
+            found,
+        })
+        .collect();
+    if !problems.is_empty() {
+        return problems;
+    }
+
+    let grid = Grid::from_lines(rows.into_iter().map(|(_, line)| line), |c| c).unwrap();
     let map = build_direction_map();
+    let step = |point, direction: Direction| grid.offset(point, direction.delta());
 
-    if let Some(start) = start {
-        // We check all four directions (DFS), we know that only starting two directions will complete
-        // the same loop
-        let base = [
-            Direction::Up,
-            Direction::Down,
-            Direction::Left,
-            Direction::Right,
-        ];
+    let mut starts = grid.points().filter(|&point| grid[point] == 'S');
+    let Some(start) = starts.next() else {
+        problems.push(Problem::MissingStart);
+        return problems;
+    };
+    problems.extend(starts.map(Problem::DuplicateStart));
 
-        let loop_length = base
-            .iter()
-            .filter_map(|&dir| find_loop_length(input, start, dir, &map))
-            .find(|&len| len != 0)
-            .unwrap_or(0);
-        loop_length / 2
-    } else {
-        panic!("Start not found")
+    let exits: Vec<Direction> = Direction::ALL
+        .into_iter()
+        .filter(|&direction| {
+            step(start, direction).is_some_and(|point| map.contains_key(&(direction, grid[point])))
+        })
+        .collect();
+    if exits.len() != 2 {
+        problems.push(Problem::StartConnections {
+            start,
+            count: exits.len(),
+        });
+    }
+
+    // Follow the pipes out of `S` both ways, they should lead back around to it
+    let mut on_loop = HashSet::from([start]);
+    'exit: for mut direction in exits {
+        let mut point = step(start, direction).unwrap();
+        loop {
+            on_loop.insert(point);
+            direction = map[&(direction, grid[point])];
+            match step(point, direction) {
+                None => {
+                    problems.push(Problem::OffGrid {
+                        at: point,
+                        tile: grid[point],
+                        on_loop: true,
+                    });
+                    break;
+                }
+                Some(next) if grid[next] == 'S' => break 'exit,
+                Some(next) if map.contains_key(&(direction, grid[next])) => point = next,
+                Some(_) => {
+                    problems.push(Problem::DeadEnd {
+                        at: point,
+                        direction,
+                    });
+                    break;
+                }
+            }
+        }
+    }
+
+    // Pipes that are not part of the loop, a pipe connects to a side if it can be entered from it
+    for (point, &tile) in grid.iter() {
+        if on_loop.contains(&point) {
+            continue;
+        }
+        let points_off_grid = Direction::ALL.into_iter().any(|direction| {
+            map.contains_key(&(direction.opposite(), tile)) && step(point, direction).is_none()
+        });
+        if points_off_grid {
+            problems.push(Problem::OffGrid {
+                at: point,
+                tile,
+                on_loop: false,
+            });
+        }
     }
+
+    problems
+}
+
+fn input() -> Vec<String> {
+    std::io::stdin()
+        .lock()
+        .lines()
+        .map(|line| line.unwrap())
+        .collect()
 }
 
 fn main() {
     let time = Instant::now();
-    let input = input();
-    part1(solve(&input));
-    part2(&input);
+    let lines = input();
+    let args: Vec<String> = std::env::args().collect();
+
+    // Report every problem with the maze, including junk pipes pointing off the grid: --validate
+    let problems = validate(&lines);
+    if args.iter().any(|arg| arg == "--validate") {
+        for problem in &problems {
+            println!("{}", problem);
+        }
+        if problems.is_empty() {
+            println!("No problems found");
+        }
+        return;
+    }
+    if problems.iter().any(Problem::is_fatal) {
+        for problem in problems.iter().filter(|problem| problem.is_fatal()) {
+            eprintln!("{}", problem);
+        }
+        std::process::exit(1);
+    }
+
+    let input = Grid::from_lines(&lines, |c| c).unwrap();
+    let pipe_loop = PipeLoop::find(&input).expect("Loop not found");
+
+    // Print the loop tiles in walking order with their distances from `S`: --loop
+    if args.iter().any(|arg| arg == "--loop") {
+        println!("S is a '{}' pipe", pipe_loop.start_tile);
+        for &point in &pipe_loop.path {
+            let (forward, backward) = pipe_loop.distances(point).unwrap();
+            println!(
+                "{:?} {} {} {}",
+                point,
+                pipe_loop.tile(&input, point),
+                forward,
+                backward
+            );
+        }
+        return;
+    }
+
+    // Draw the maze with the loop and enclosed tiles highlighted: --render [--color]
+    if args.iter().any(|arg| arg == "--render") {
+        let color = args.iter().any(|arg| arg == "--color");
+        print!("{}", render(&input, &pipe_loop, color));
+        return;
+    }
+
+    // Assert all enclosed area strategies agree: --check
+    if args.iter().any(|arg| arg == "--check") {
+        let results = Strategy::ALL.map(|strategy| enclosed_tiles(&input, &pipe_loop, strategy));
+        for (strategy, result) in Strategy::ALL.iter().zip(results) {
+            println!("{:?}: {}", strategy, result);
+        }
+        assert!(
+            results.iter().all(|&result| result == results[0]),
+            "Strategies disagree on the enclosed tile count"
+        );
+        return;
+    }
+
+    // Pick the enclosed area strategy for part 2: --strategy <scanline|shoelace|flood|flood3>
+    let strategy = args
+        .iter()
+        .position(|arg| arg == "--strategy")
+        .map(|i| {
+            args.get(i + 1)
+                .expect("--strategy expects a name")
+                .parse()
+                .unwrap()
+        })
+        .unwrap_or(Strategy::Scanline);
+
+    part1(&pipe_loop);
+    part2(&input, &pipe_loop, strategy);
     println!("Time elapsed is {:?}", time.elapsed())
 }
 
-fn part1(result: usize) {
-    println!("Part 1 answer: {:?}", result);
+fn part1(pipe_loop: &PipeLoop) {
+    println!("Part 1 answer: {:?}", pipe_loop.farthest_distance());
 }
 
-fn part2(input: &[String]) {
-    // Find the starting position in the grid
-    let start = input
-        .iter()
-        .enumerate()
-        .find_map(|(i, line)| line.chars().position(|c| c == 'S').map(|j| (i, j)));
-    let map = build_direction_map();
+fn part2(input: &Grid<char>, pipe_loop: &PipeLoop, strategy: Strategy) {
+    let result = enclosed_tiles(input, pipe_loop, strategy);
+    println!("Part 2 answer: {:?}", result);
+}
 
-    let result = if let Some(start) = start {
-        let lengths = calculate_lengths(input, start, &map);
-        count_points_inside(input, &lengths, start)
-    } else {
-        panic!("Start not found")
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    println!("Part 2 answer: {:?}", result);
+    /// Every example with its enclosed tile count
+    const EXAMPLES: [(&str, usize); 11] = [
+        (include_str!("../example.txt"), 1),
+        (include_str!("../example2.txt"), 4),
+        (include_str!("../example3.txt"), 4),
+        (include_str!("../example4.txt"), 8),
+        (include_str!("../example5.txt"), 10),
+        (include_str!("../example6.txt"), 1),
+        (include_str!("../example7.txt"), 1),
+        (include_str!("../example8.txt"), 3),
+        (include_str!("../example9.txt"), 2),
+        (include_str!("../example10.txt"), 2),
+        (include_str!("../example11.txt"), 6),
+    ];
+
+    fn parse(text: &str) -> (Grid<char>, PipeLoop) {
+        let grid = Grid::from_lines(text.lines(), |c| c).unwrap();
+        let pipe_loop = PipeLoop::find(&grid).expect("Loop not found");
+        (grid, pipe_loop)
+    }
+
+    #[test]
+    fn loops_on_edges() {
+        // Farthest distance and enclosed tiles of the loops flush against the grid edges
+        let expected = [(4, 1), (4, 1), (6, 3), (5, 2), (5, 2), (9, 6)];
+        for (i, (&(text, _), (farthest, enclosed))) in
+            EXAMPLES[5..].iter().zip(expected).enumerate()
+        {
+            let (grid, pipe_loop) = parse(text);
+            assert_eq!(farthest, pipe_loop.farthest_distance(), "example {}", i + 6);
+            assert_eq!(
+                enclosed,
+                enclosed_tiles(&grid, &pipe_loop, Strategy::Scanline),
+                "example {}",
+                i + 6
+            );
+        }
+    }
+
+    #[test]
+    fn strategies_agree() {
+        for (i, (text, expected)) in EXAMPLES.iter().enumerate() {
+            let (grid, pipe_loop) = parse(text);
+            for strategy in Strategy::ALL {
+                assert_eq!(
+                    *expected,
+                    enclosed_tiles(&grid, &pipe_loop, strategy),
+                    "{:?} on example {}",
+                    strategy,
+                    i + 1
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn render_plain() {
+        let (grid, pipe_loop) = parse("S-7\n|.|\nL-J");
+        assert_eq!("┌─┐\n│I│\n└─┘\n", render(&grid, &pipe_loop, false));
+
+        // `S` is drawn as the `F` it stands for, tiles squeezed between pipes are outside
+        let (grid, pipe_loop) = parse(include_str!("../example2.txt"));
+        assert_eq!(
+            "OOOOOOOOOOO\n\
+             O┌───────┐O\n\
+             O│┌─────┐│O\n\
+             O││OOOOO││O\n\
+             O││OOOOO││O\n\
+             O│└─┐O┌─┘│O\n\
+             O│II│O│II│O\n\
+             O└──┘O└──┘O\n\
+             OOOOOOOOOOO\n",
+            render(&grid, &pipe_loop, false)
+        );
+    }
+
+    fn problems(maze: &str) -> Vec<Problem> {
+        validate(&maze.lines().map(String::from).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn ragged_rows_are_numbered_by_input_line() {
+        assert_eq!(
+            vec![Problem::RaggedRow {
+                row: 3,
+                expected: 3,
+                found: 2
+            }],
+            problems("S-7\n\n|.|\nL-")
+        );
+    }
+
+    #[test]
+    fn valid_maze_has_no_problems() {
+        assert_eq!(Vec::<Problem>::new(), problems("S-7\n|.|\nL-J"));
+        for (i, (text, _)) in EXAMPLES.iter().enumerate() {
+            let fatal: Vec<Problem> = problems(text)
+                .into_iter()
+                .filter(Problem::is_fatal)
+                .collect();
+            assert!(fatal.is_empty(), "example {}: {:?}", i + 1, fatal);
+        }
+    }
+
+    #[test]
+    fn start_problems() {
+        assert_eq!(vec![Problem::MissingStart], problems(".F7\n.LJ"));
+        assert_eq!(
+            vec![Problem::DuplicateStart((2, 2))],
+            problems("S-7\n|.|\nL-S")
+        );
+        assert_eq!(
+            vec![
+                Problem::StartConnections {
+                    start: (0, 0),
+                    count: 1
+                },
+                Problem::DeadEnd {
+                    at: (2, 0),
+                    direction: Direction::Up
+                }
+            ],
+            problems("S-7\n..|\nL-J")
+        );
+    }
+
+    #[test]
+    fn dead_ends() {
+        assert_eq!(
+            vec![
+                Problem::DeadEnd {
+                    at: (2, 1),
+                    direction: Direction::Right
+                },
+                Problem::DeadEnd {
+                    at: (1, 2),
+                    direction: Direction::Down
+                }
+            ],
+            problems("S-7\n|.|\nL-.")
+        );
+    }
+
+    #[test]
+    fn pipes_off_the_grid() {
+        // The loop leaves through the top of the `J` one way and dead-ends below it the other
+        let found = problems("S-J\n|.|\nL-J");
+        assert_eq!(
+            vec![
+                Problem::DeadEnd {
+                    at: (1, 2),
+                    direction: Direction::Up
+                },
+                Problem::OffGrid {
+                    at: (0, 2),
+                    tile: 'J',
+                    on_loop: true
+                }
+            ],
+            found
+        );
+        assert!(found.iter().all(Problem::is_fatal));
+
+        // A junk pipe pointing off the grid does not keep the loop from closing
+        let found = problems("S-7|\n|.|.\nL-J.");
+        assert_eq!(
+            vec![Problem::OffGrid {
+                at: (0, 3),
+                tile: '|',
+                on_loop: false
+            }],
+            found
+        );
+        assert!(!found[0].is_fatal());
+    }
 }
@@ -1,91 +1,852 @@
-use std::{collections::HashSet, io::BufRead, time::Instant};
-
-type GalaxyPairs = Vec<((usize, usize), (usize, usize))>;
-
-fn input() -> (GalaxyPairs, HashSet<usize>, HashSet<usize>) {
-    let input: Vec<String> = std::io::stdin()
-        .lock()
-        .lines()
-        .map(|line| line.unwrap())
-        .collect();
-
-    // Track universe expansion and galaxies
-    let mut expand_rows = HashSet::new();
-    let mut expand_cols = HashSet::new();
-    let mut galaxies: Vec<(usize, usize)> = Vec::new();
-    for (y, row) in input.iter().enumerate() {
-        if !row.contains('#') {
-            expand_rows.insert(y);
+use std::{cmp::Ordering, fmt, io::BufRead, str::FromStr, time::Instant};
+
+use grid::{Grid, GridError, Point};
+use serde::Serialize;
+
+#[derive(Debug)]
+enum ImageError {
+    Grid(GridError),
+    UnexpectedChar { at: Point, ch: char },
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::Grid(err) => write!(f, "Invalid image: {}", err),
+            ImageError::UnexpectedChar { at, ch } => {
+                write!(f, "Invalid image: unexpected '{}' at {:?}", ch, at)
+            }
         }
-        for (x, ch) in row.chars().enumerate() {
-            if ch == '.' && !input.iter().any(|row| row.chars().nth(x) == Some('#')) {
-                expand_cols.insert(x);
-            } else if ch == '#' {
+    }
+}
+
+type Image = (Vec<Point>, Vec<usize>, Vec<usize>);
+
+/// Parse the image into its galaxies and the sorted rows and columns without any galaxy
+fn parse_image(lines: impl Iterator<Item = String>) -> Result<Image, ImageError> {
+    let image = Grid::from_lines(lines, |c| c).map_err(ImageError::Grid)?;
+
+    // Track universe expansion and galaxies in a single pass
+    let mut occupied_rows = vec![false; image.height()];
+    let mut occupied_cols = vec![false; image.width()];
+    let mut galaxies: Vec<Point> = Vec::new();
+    for ((y, x), &ch) in image.iter() {
+        match ch {
+            '.' => (),
+            '#' => {
+                occupied_rows[y] = true;
+                occupied_cols[x] = true;
                 galaxies.push((y, x));
             }
+            ch => return Err(ImageError::UnexpectedChar { at: (y, x), ch }),
         }
     }
 
-    let pairs: GalaxyPairs = galaxies
+    let empty = |occupied: Vec<bool>| -> Vec<usize> {
+        occupied
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, occupied)| (!occupied).then_some(i))
+            .collect()
+    };
+    Ok((galaxies, empty(occupied_rows), empty(occupied_cols)))
+}
+
+fn input() -> Result<Image, ImageError> {
+    parse_image(std::io::stdin().lock().lines().map(|line| line.unwrap()))
+}
+
+/// Sum of the distances between every pair of values, after sorting each value is larger than all
+/// values before it so it contributes `value * index - sum of the values before it`
+fn sum_pairwise_distances(mut values: Vec<usize>) -> u128 {
+    values.sort_unstable();
+    let mut prefix_sum = 0;
+    values
         .iter()
         .enumerate()
-        .flat_map(|(i, loc1)| galaxies.iter().skip(i + 1).map(|loc2| (*loc1, *loc2)))
-        .collect();
+        .map(|(i, &value)| {
+            let distances = value as u128 * i as u128 - prefix_sum;
+            prefix_sum += value as u128;
+            distances
+        })
+        .sum()
+}
 
-    (pairs, expand_rows, expand_cols)
+/// How many times larger every empty row and column becomes, 2 means each one is doubled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+struct Expansion {
+    rows: usize,
+    cols: usize,
 }
 
-fn get_shortest_galaxy_paths(
-    galaxy_pairs: &GalaxyPairs,
-    expand_rows: &HashSet<usize>,
-    expand_cols: &HashSet<usize>,
-    expansion_factor: usize,
-) -> isize {
-    // Factor in the expansion of the universe
-    let get_expanded_point =
-        |original: usize, expansions: &HashSet<usize>, expansion_factor: usize| -> usize {
-            original + (expansions.iter().filter(|&&e| e < original).count() * expansion_factor)
+impl Expansion {
+    fn uniform(factor: usize) -> Self {
+        Expansion {
+            rows: factor,
+            cols: factor,
+        }
+    }
+
+    /// Read `--expand <factor>`, `--expand-rows <factor>` and `--expand-cols <factor>`, the per-axis
+    /// flags take precedence
+    fn from_args(args: &[String]) -> Option<Self> {
+        let factor = |flag: &str| {
+            let i = args.iter().position(|arg| arg == flag)?;
+            let factor: usize = args
+                .get(i + 1)
+                .and_then(|n| n.parse().ok())
+                .filter(|&n| n >= 1)
+                .unwrap_or_else(|| panic!("{} expects a factor of at least 1", flag));
+            Some(factor)
         };
+        let both = factor("--expand");
+        let rows = factor("--expand-rows").or(both);
+        let cols = factor("--expand-cols").or(both);
+        if rows.is_none() && cols.is_none() {
+            return None;
+        }
+        Some(Expansion {
+            rows: rows.unwrap_or(1),
+            cols: cols.unwrap_or(1),
+        })
+    }
+}
+
+/// Number of empty rows or columns before `original`, `expansions` is sorted
+fn empty_before(original: usize, expansions: &[usize]) -> usize {
+    expansions.partition_point(|&e| e < original)
+}
 
-    // Sum the shortest path
+/// The sum of the shortest paths between all galaxy pairs is linear in the expansion factors:
+/// `base + (rows - 1) * per_row + (cols - 1) * per_col`. Expanding keeps galaxies in the same
+/// order, so every pair crosses the same empty rows and columns whatever the factor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DistanceSum {
+    /// Total distance in the unexpanded image
+    base: u128,
+    /// Empty rows crossed over all pairs, each adds `rows - 1` to the total
+    per_row: u128,
+    /// Empty columns crossed over all pairs, each adds `cols - 1` to the total
+    per_col: u128,
+}
+
+fn get_shortest_galaxy_paths(
+    galaxies: &[Point],
+    expand_rows: &[usize],
+    expand_cols: &[usize],
+) -> DistanceSum {
+    // Sum the shortest path, the taxicab distance is the sum of the distances on each axis
     // https://en.wikipedia.org/wiki/Taxicab_geometry
-    galaxy_pairs
-        .iter()
-        .map(|(loc1, loc2)| {
-            let loc1_expanded = (
-                get_expanded_point(loc1.0, expand_rows, expansion_factor),
-                get_expanded_point(loc1.1, expand_cols, expansion_factor),
-            );
-            let loc2_expanded = (
-                get_expanded_point(loc2.0, expand_rows, expansion_factor),
-                get_expanded_point(loc2.1, expand_cols, expansion_factor),
-            );
-            (loc1_expanded.1 as isize - loc2_expanded.1 as isize).abs()
-                + (loc1_expanded.0 as isize - loc2_expanded.0 as isize).abs()
+    let axis = |coord: fn(&Point) -> usize, expansions: &[usize]| {
+        let original = galaxies.iter().map(coord).collect();
+        let empty = galaxies
+            .iter()
+            .map(|galaxy| empty_before(coord(galaxy), expansions))
+            .collect();
+        (
+            sum_pairwise_distances(original),
+            sum_pairwise_distances(empty),
+        )
+    };
+    let (row_base, per_row) = axis(|&(y, _)| y, expand_rows);
+    let (col_base, per_col) = axis(|&(_, x)| x, expand_cols);
+
+    DistanceSum {
+        base: row_base + col_base,
+        per_row,
+        per_col,
+    }
+}
+
+/// How the distance between two galaxies is measured after expansion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Metric {
+    /// Steps up, down, left and right, the puzzle's shortest path
+    /// https://en.wikipedia.org/wiki/Taxicab_geometry
+    #[default]
+    Manhattan,
+    /// Steps that may also go diagonally
+    /// https://en.wikipedia.org/wiki/Chebyshev_distance
+    Chebyshev,
+    SquaredEuclidean,
+    Euclidean,
+}
+
+impl Metric {
+    fn distance(self, a: Point, b: Point) -> Distance {
+        let (dy, dx) = (a.0.abs_diff(b.0) as u128, a.1.abs_diff(b.1) as u128);
+        match self {
+            Metric::Manhattan => Distance::Exact(dy + dx),
+            Metric::Chebyshev => Distance::Exact(dy.max(dx)),
+            Metric::SquaredEuclidean => Distance::Exact(dy * dy + dx * dx),
+            Metric::Euclidean => Distance::Real(((dy * dy + dx * dx) as f64).sqrt()),
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "manhattan" => Ok(Metric::Manhattan),
+            "chebyshev" => Ok(Metric::Chebyshev),
+            "squared-euclidean" => Ok(Metric::SquaredEuclidean),
+            "euclidean" => Ok(Metric::Euclidean),
+            _ => Err(format!("Unknown metric '{}'", s)),
+        }
+    }
+}
+
+/// A distance or a sum of distances, exact for every metric but the Euclidean one
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(untagged)]
+enum Distance {
+    Exact(u128),
+    Real(f64),
+}
+
+impl Distance {
+    fn as_f64(self) -> f64 {
+        match self {
+            Distance::Exact(distance) => distance as f64,
+            Distance::Real(distance) => distance,
+        }
+    }
+
+    fn total_cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Distance::Exact(a), Distance::Exact(b)) => a.cmp(b),
+            _ => self.as_f64().total_cmp(&other.as_f64()),
+        }
+    }
+}
+
+impl fmt::Display for Distance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Distance::Exact(distance) => write!(f, "{}", distance),
+            Distance::Real(distance) => write!(f, "{}", distance),
+        }
+    }
+}
+
+/// Sum of the squared distances between every pair of values: `n * sum(x^2) - sum(x)^2`
+fn sum_pairwise_squares(values: impl Iterator<Item = usize>) -> u128 {
+    let (mut n, mut sum, mut sum_of_squares) = (0, 0, 0);
+    for value in values {
+        n += 1;
+        sum += value as u128;
+        sum_of_squares += value as u128 * value as u128;
+    }
+    n * sum_of_squares - sum * sum
+}
+
+/// Galaxy positions after expansion, galaxies are numbered from 1 in reading order like the puzzle
+struct ExpandedGalaxies {
+    positions: Vec<Point>,
+    metric: Metric,
+}
+
+#[derive(Debug, Serialize)]
+struct Neighbor {
+    id: usize,
+    distance: Distance,
+}
+
+#[derive(Debug, Serialize)]
+struct Pair {
+    a: usize,
+    b: usize,
+    distance: Distance,
+}
+
+/// Number of pairs with a distance in `from..to`, the last bin of real distances also includes `to`
+#[derive(Debug, Serialize)]
+struct Bin {
+    from: Distance,
+    to: Distance,
+    count: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct GalaxyReport {
+    id: usize,
+    position: Point,
+    distances: Vec<Neighbor>,
+    nearest: Vec<Neighbor>,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    expansion: Expansion,
+    metric: Metric,
+    galaxies: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    galaxy: Option<GalaxyReport>,
+    farthest_pair: Option<Pair>,
+    histogram: Vec<Bin>,
+}
+
+impl ExpandedGalaxies {
+    fn new(
+        galaxies: &[Point],
+        expand_rows: &[usize],
+        expand_cols: &[usize],
+        expansion: Expansion,
+        metric: Metric,
+    ) -> Self {
+        let expand = |original: usize, expansions: &[usize], factor: usize| {
+            original + empty_before(original, expansions) * (factor - 1)
+        };
+        let positions = galaxies
+            .iter()
+            .map(|&(y, x)| {
+                (
+                    expand(y, expand_rows, expansion.rows),
+                    expand(x, expand_cols, expansion.cols),
+                )
+            })
+            .collect();
+        ExpandedGalaxies { positions, metric }
+    }
+
+    fn position(&self, id: usize) -> Option<Point> {
+        id.checked_sub(1)
+            .and_then(|i| self.positions.get(i))
+            .copied()
+    }
+
+    fn distance(&self, a: Point, b: Point) -> Distance {
+        self.metric.distance(a, b)
+    }
+
+    /// Sum of the distances between all pairs, without going over every pair where the metric
+    /// allows it
+    fn total_distance(&self) -> Distance {
+        let rows = || self.positions.iter().map(|&(y, _)| y);
+        let cols = || self.positions.iter().map(|&(_, x)| x);
+        match self.metric {
+            Metric::Manhattan => Distance::Exact(
+                sum_pairwise_distances(rows().collect()) + sum_pairwise_distances(cols().collect()),
+            ),
+            Metric::Chebyshev => {
+                // max(|dy|, |dx|) = (|dy + dx| + |dy - dx|) / 2, the axes are rotated by 45
+                // degrees and shifted to keep the coordinates positive
+                let max_col = cols().max().unwrap_or(0);
+                let sum = self.positions.iter().map(|&(y, x)| y + x).collect();
+                let difference = self
+                    .positions
+                    .iter()
+                    .map(|&(y, x)| y + max_col - x)
+                    .collect();
+                Distance::Exact(
+                    (sum_pairwise_distances(sum) + sum_pairwise_distances(difference)) / 2,
+                )
+            }
+            Metric::SquaredEuclidean => {
+                Distance::Exact(sum_pairwise_squares(rows()) + sum_pairwise_squares(cols()))
+            }
+            Metric::Euclidean => {
+                Distance::Real(self.pairs().map(|pair| pair.distance.as_f64()).sum())
+            }
+        }
+    }
+
+    /// Distances from galaxy `id` to every other galaxy, by id
+    fn distances_from(&self, id: usize) -> Option<Vec<Neighbor>> {
+        let position = self.position(id)?;
+        Some(
+            self.positions
+                .iter()
+                .enumerate()
+                .map(|(i, &other)| Neighbor {
+                    id: i + 1,
+                    distance: self.distance(position, other),
+                })
+                .filter(|neighbor| neighbor.id != id)
+                .collect(),
+        )
+    }
+
+    /// The `k` galaxies closest to galaxy `id`, ties are broken by id
+    fn nearest(&self, id: usize, k: usize) -> Option<Vec<Neighbor>> {
+        let mut neighbors = self.distances_from(id)?;
+        neighbors.sort_by(|a, b| a.distance.total_cmp(&b.distance).then(a.id.cmp(&b.id)));
+        neighbors.truncate(k);
+        Some(neighbors)
+    }
+
+    fn pairs(&self) -> impl Iterator<Item = Pair> + '_ {
+        self.positions.iter().enumerate().flat_map(move |(i, &a)| {
+            self.positions
+                .iter()
+                .enumerate()
+                .skip(i + 1)
+                .map(move |(j, &b)| Pair {
+                    a: i + 1,
+                    b: j + 1,
+                    distance: self.distance(a, b),
+                })
         })
-        .sum()
+    }
+
+    /// The pair furthest apart, the first one in reading order on ties
+    fn farthest_pair(&self) -> Option<Pair> {
+        self.pairs().reduce(|best, pair| {
+            if pair.distance.total_cmp(&best.distance).is_gt() {
+                pair
+            } else {
+                best
+            }
+        })
+    }
+
+    /// Pairwise distances counted in `bins` bins of equal width starting from 0 up to `max`, the
+    /// largest distance, exact distances get whole-number bins wide enough to hold it
+    fn histogram(&self, max: Distance, bins: usize) -> Vec<Bin> {
+        let bins = bins.max(1);
+        let (edges, bin_of): (Vec<Distance>, Box<dyn Fn(Distance) -> usize>) = match max {
+            Distance::Exact(max) => {
+                let width = max / bins as u128 + 1;
+                let edges = (0..=max / width + 1)
+                    .map(|i| Distance::Exact(i * width))
+                    .collect();
+                let bin_of = move |distance| match distance {
+                    Distance::Exact(distance) => (distance / width) as usize,
+                    Distance::Real(_) => unreachable!("A metric is either exact or not"),
+                };
+                (edges, Box::new(bin_of))
+            }
+            Distance::Real(max) => {
+                let width = if max > 0.0 { max / bins as f64 } else { 1.0 };
+                let edges = (0..=bins)
+                    .map(|i| Distance::Real(i as f64 * width))
+                    .collect();
+                let bin_of =
+                    move |distance: Distance| ((distance.as_f64() / width) as usize).min(bins - 1);
+                (edges, Box::new(bin_of))
+            }
+        };
+        let mut histogram: Vec<Bin> = edges
+            .windows(2)
+            .map(|edge| Bin {
+                from: edge[0],
+                to: edge[1],
+                count: 0,
+            })
+            .collect();
+        // Count every pair in place, collecting them would take memory quadratic in the galaxies
+        for pair in self.pairs() {
+            histogram[bin_of(pair.distance)].count += 1;
+        }
+        histogram
+    }
 }
 
 fn main() {
     let time = Instant::now();
-    let (galaxy_pairs, expand_rows, expand_cols) = input();
-    part1(&galaxy_pairs, &expand_rows, &expand_cols);
-    part2(&galaxy_pairs, &expand_rows, &expand_cols);
+    let (galaxies, expand_rows, expand_cols) = input().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    // Measure distances with another metric after expansion:
+    // --metric <manhattan|chebyshev|squared-euclidean|euclidean>
+    let args: Vec<String> = std::env::args().collect();
+    let metric = args
+        .iter()
+        .position(|arg| arg == "--metric")
+        .map(|i| {
+            args.get(i + 1)
+                .expect("--metric expects a name")
+                .parse()
+                .unwrap()
+        })
+        .unwrap_or_default();
+
+    // Report pairwise distances as JSON, optionally for a single galaxy:
+    // --report [--galaxy <id>] [--nearest <k>] [--bins <n>], expanded like part 1 unless specified
+    if args.iter().any(|arg| arg == "--report") {
+        let value = |flag: &str| -> Option<usize> {
+            let i = args.iter().position(|arg| arg == flag)?;
+            Some(
+                args.get(i + 1)
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or_else(|| panic!("{} expects a number", flag)),
+            )
+        };
+        let expansion = Expansion::from_args(&args).unwrap_or(Expansion::uniform(2));
+        let expanded =
+            ExpandedGalaxies::new(&galaxies, &expand_rows, &expand_cols, expansion, metric);
+        let galaxy = value("--galaxy").map(|id| {
+            let Some(position) = expanded.position(id) else {
+                eprintln!(
+                    "Galaxy {} not found, galaxies are numbered 1 to {}",
+                    id,
+                    galaxies.len()
+                );
+                std::process::exit(1);
+            };
+            GalaxyReport {
+                id,
+                position,
+                distances: expanded.distances_from(id).unwrap(),
+                nearest: expanded
+                    .nearest(id, value("--nearest").unwrap_or(5))
+                    .unwrap(),
+            }
+        });
+        let farthest_pair = expanded.farthest_pair();
+        let histogram = farthest_pair
+            .as_ref()
+            .map(|pair| expanded.histogram(pair.distance, value("--bins").unwrap_or(10)))
+            .unwrap_or_default();
+        let report = Report {
+            expansion,
+            metric,
+            galaxies: galaxies.len(),
+            galaxy,
+            farthest_pair,
+            histogram,
+        };
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        return;
+    }
+
+    // Evaluate a custom expansion: --expand <factor> or --expand-rows <factor> --expand-cols <factor>
+    let image = (galaxies, expand_rows, expand_cols);
+    if let Some(expansion) = Expansion::from_args(&args) {
+        if metric == Metric::Manhattan {
+            let (galaxies, expand_rows, expand_cols) = &image;
+            let distances = get_shortest_galaxy_paths(galaxies, expand_rows, expand_cols);
+            println!(
+                "Total distance = {} + (rows - 1) * {} + (cols - 1) * {}",
+                distances.base, distances.per_row, distances.per_col
+            );
+        }
+        println!(
+            "Total distance for rows x{}, cols x{}: {}",
+            expansion.rows,
+            expansion.cols,
+            total_for_expansion(&image, expansion, metric)
+        );
+        return;
+    }
+
+    part1(&image, metric);
+    part2(&image, metric);
     println!("Time elapsed is {:?}", time.elapsed())
 }
 
-fn part1(galaxy_pairs: &GalaxyPairs, expand_rows: &HashSet<usize>, expand_cols: &HashSet<usize>) {
-    const EXPANSION_FACTOR: usize = 1;
+/// The sum of the distances between all galaxy pairs after `expansion`
+fn total_for_expansion(
+    (galaxies, expand_rows, expand_cols): &Image,
+    expansion: Expansion,
+    metric: Metric,
+) -> Distance {
+    ExpandedGalaxies::new(galaxies, expand_rows, expand_cols, expansion, metric).total_distance()
+}
+
+fn part1(image: &Image, metric: Metric) {
+    const EXPANSION_FACTOR: usize = 2;
     println!(
-        "Part 1 answer: {:?}",
-        get_shortest_galaxy_paths(galaxy_pairs, expand_rows, expand_cols, EXPANSION_FACTOR)
+        "Part 1 answer: {}",
+        total_for_expansion(image, Expansion::uniform(EXPANSION_FACTOR), metric)
     );
 }
 
-fn part2(galaxy_pairs: &GalaxyPairs, expand_rows: &HashSet<usize>, expand_cols: &HashSet<usize>) {
-    const EXPANSION_FACTOR: usize = 999_999;
+fn part2(image: &Image, metric: Metric) {
+    const EXPANSION_FACTOR: usize = 1_000_000;
     println!(
-        "Part 2 answer: {:?}",
-        get_shortest_galaxy_paths(galaxy_pairs, expand_rows, expand_cols, EXPANSION_FACTOR)
+        "Part 2 answer: {}",
+        total_for_expansion(image, Expansion::uniform(EXPANSION_FACTOR), metric)
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(image: &str) -> Result<Image, ImageError> {
+        parse_image(image.lines().map(String::from))
+    }
+
+    #[test]
+    fn finds_empty_rows_and_columns_on_example() {
+        let (galaxies, expand_rows, expand_cols) = parse(include_str!("../example.txt")).unwrap();
+        assert_eq!(9, galaxies.len());
+        assert_eq!((0, 3), galaxies[0]);
+        assert_eq!(vec![3, 7], expand_rows);
+        assert_eq!(vec![2, 5, 8], expand_cols);
+    }
+
+    #[test]
+    fn rejects_ragged_rows() {
+        let err = parse("#..\n\n.#\n").unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ImageError::Grid(GridError::Ragged {
+                    row: 2,
+                    expected: 3,
+                    found: 2
+                })
+            ),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn rejects_unexpected_characters() {
+        let err = parse("#..\n.X.\n").unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ImageError::UnexpectedChar {
+                    at: (1, 1),
+                    ch: 'X'
+                }
+            ),
+            "{:?}",
+            err
+        );
+    }
+
+    fn brute_force_total(expanded: &ExpandedGalaxies) -> u128 {
+        expanded
+            .pairs()
+            .map(|pair| match pair.distance {
+                Distance::Exact(distance) => distance,
+                Distance::Real(_) => panic!("Expected an exact distance"),
+            })
+            .sum()
+    }
+
+    /// Evaluate the printed relation `base + (rows - 1) * per_row + (cols - 1) * per_col`
+    fn linear_total(distances: &DistanceSum, expansion: Expansion) -> u128 {
+        distances.base
+            + (expansion.rows as u128 - 1) * distances.per_row
+            + (expansion.cols as u128 - 1) * distances.per_col
+    }
+
+    #[test]
+    fn linear_model_on_example() {
+        let image = parse(include_str!("../example.txt")).unwrap();
+        let (galaxies, expand_rows, expand_cols) = &image;
+        let distances = get_shortest_galaxy_paths(galaxies, expand_rows, expand_cols);
+        assert_eq!(
+            DistanceSum {
+                base: 292,
+                per_row: 36,
+                per_col: 46
+            },
+            distances
+        );
+        for (factor, expected) in [(2, 374), (10, 1030), (100, 8410)] {
+            let expansion = Expansion::uniform(factor);
+            assert_eq!(expected, linear_total(&distances, expansion));
+            assert_eq!(
+                Distance::Exact(expected),
+                total_for_expansion(&image, expansion, Metric::Manhattan)
+            );
+        }
+
+        let expansion = Expansion { rows: 3, cols: 100 };
+        let expanded = ExpandedGalaxies::new(
+            galaxies,
+            expand_rows,
+            expand_cols,
+            expansion,
+            Metric::Manhattan,
+        );
+        assert_eq!(
+            brute_force_total(&expanded),
+            linear_total(&distances, expansion)
+        );
+    }
+
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn expansion_from_args() {
+        assert_eq!(
+            None,
+            Expansion::from_args(&args("day11 --metric chebyshev"))
+        );
+        assert_eq!(
+            Some(Expansion::uniform(10)),
+            Expansion::from_args(&args("day11 --expand 10"))
+        );
+        assert_eq!(
+            Some(Expansion { rows: 3, cols: 10 }),
+            Expansion::from_args(&args("day11 --expand 10 --expand-rows 3"))
+        );
+        assert_eq!(
+            Some(Expansion { rows: 1, cols: 7 }),
+            Expansion::from_args(&args("day11 --expand-cols 7"))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "--expand-rows expects a factor of at least 1")]
+    fn expansion_rejects_zero() {
+        Expansion::from_args(&args("day11 --expand-rows 0"));
+    }
+
+    #[test]
+    fn prefix_sums_with_repeated_values() {
+        let values: Vec<usize> = vec![3, 1, 3, 3, 0, 1, 7, 3];
+        let expected: u128 = (0..values.len())
+            .flat_map(|i| (i + 1..values.len()).map(move |j| (i, j)))
+            .map(|(i, j)| values[i].abs_diff(values[j]) as u128)
+            .sum();
+        assert_eq!(expected, sum_pairwise_distances(values));
+        assert_eq!(0, sum_pairwise_distances(vec![4; 5]));
+        assert_eq!(0, sum_pairwise_distances(vec![]));
+    }
+
+    #[test]
+    fn galaxies_sharing_rows_and_columns() {
+        let image = parse("#.#..#\n......\n#.#...\n......\n..#..#\n").unwrap();
+        let (galaxies, expand_rows, expand_cols) = &image;
+        assert_eq!(vec![1, 3], *expand_rows);
+        assert_eq!(vec![1, 3, 4], *expand_cols);
+        let distances = get_shortest_galaxy_paths(galaxies, expand_rows, expand_cols);
+        for expansion in [
+            Expansion::uniform(1),
+            Expansion::uniform(2),
+            Expansion { rows: 3, cols: 100 },
+        ] {
+            let expanded = ExpandedGalaxies::new(
+                galaxies,
+                expand_rows,
+                expand_cols,
+                expansion,
+                Metric::Manhattan,
+            );
+            assert_eq!(
+                brute_force_total(&expanded),
+                linear_total(&distances, expansion),
+                "{:?}",
+                expansion
+            );
+        }
+    }
+
+    #[test]
+    fn closed_form_sums_match_brute_force() {
+        let (galaxies, expand_rows, expand_cols) =
+            parse_image(include_str!("../example.txt").lines().map(String::from)).unwrap();
+        let expansions = [
+            Expansion::uniform(1),
+            Expansion::uniform(2),
+            Expansion { rows: 3, cols: 100 },
+            Expansion::uniform(1_000_000),
+        ];
+        for metric in [
+            Metric::Manhattan,
+            Metric::Chebyshev,
+            Metric::SquaredEuclidean,
+        ] {
+            for expansion in expansions {
+                let expanded =
+                    ExpandedGalaxies::new(&galaxies, &expand_rows, &expand_cols, expansion, metric);
+                assert_eq!(
+                    Distance::Exact(brute_force_total(&expanded)),
+                    expanded.total_distance(),
+                    "{:?} with {:?}",
+                    metric,
+                    expansion
+                );
+            }
+        }
+    }
+
+    fn example_expanded(metric: Metric) -> ExpandedGalaxies {
+        let (galaxies, expand_rows, expand_cols) =
+            parse_image(include_str!("../example.txt").lines().map(String::from)).unwrap();
+        ExpandedGalaxies::new(
+            &galaxies,
+            &expand_rows,
+            &expand_cols,
+            Expansion::uniform(2),
+            metric,
+        )
+    }
+
+    #[test]
+    fn distance_queries_on_example() {
+        let expanded = example_expanded(Metric::Manhattan);
+
+        let distances = expanded.distances_from(5).unwrap();
+        assert_eq!(8, distances.len());
+        assert!(distances.iter().all(|neighbor| neighbor.id != 5));
+        let to_9 = distances.iter().find(|neighbor| neighbor.id == 9).unwrap();
+        assert_eq!(Distance::Exact(9), to_9.distance);
+        assert!(expanded.distances_from(0).is_none());
+        assert!(expanded.distances_from(10).is_none());
+
+        let nearest = expanded.nearest(5, 2).unwrap();
+        let nearest: Vec<(usize, Distance)> = nearest
+            .iter()
+            .map(|neighbor| (neighbor.id, neighbor.distance))
+            .collect();
+        assert_eq!(
+            vec![(3, Distance::Exact(5)), (8, Distance::Exact(6))],
+            nearest
+        );
+
+        let farthest = expanded.farthest_pair().unwrap();
+        assert_eq!((2, 8), (farthest.a, farthest.b));
+        assert_eq!(Distance::Exact(19), farthest.distance);
+    }
+
+    #[test]
+    fn histogram_counts_every_pair() {
+        let expanded = example_expanded(Metric::Manhattan);
+        let max = expanded.farthest_pair().unwrap().distance;
+        let histogram = expanded.histogram(max, 3);
+        let bins: Vec<(Distance, Distance, usize)> = histogram
+            .iter()
+            .map(|bin| (bin.from, bin.to, bin.count))
+            .collect();
+        assert_eq!(
+            vec![
+                (Distance::Exact(0), Distance::Exact(7), 10),
+                (Distance::Exact(7), Distance::Exact(14), 16),
+                (Distance::Exact(14), Distance::Exact(21), 10),
+            ],
+            bins
+        );
+
+        // The largest real distance falls in the last bin
+        let expanded = example_expanded(Metric::Euclidean);
+        let max = expanded.farthest_pair().unwrap().distance;
+        let histogram = expanded.histogram(max, 4);
+        assert_eq!(4, histogram.len());
+        assert_eq!(36, histogram.iter().map(|bin| bin.count).sum::<usize>());
+        assert_eq!(max, histogram[3].to);
+        assert!(histogram[3].count >= 1);
+    }
+
+    #[test]
+    fn squared_euclidean_is_exact() {
+        // Large enough that an f64 would round the sum
+        let expanded = ExpandedGalaxies {
+            positions: vec![(0, 0), (3_000_000_001, 0), (0, 7_000_000_003)],
+            metric: Metric::SquaredEuclidean,
+        };
+        assert_eq!(
+            Distance::Exact(brute_force_total(&expanded)),
+            expanded.total_distance()
+        );
+        assert_eq!(
+            Distance::Exact(2 * (9_000_000_006_000_000_001 + 49_000_000_042_000_000_009)),
+            expanded.total_distance()
+        );
+    }
+}
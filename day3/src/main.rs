@@ -1,120 +1,277 @@
-use std::{collections::HashMap, io::BufRead, time::Instant};
+use std::{collections::BTreeSet, fmt, io::BufRead, ops::Range, time::Instant};
 
-type Key = (usize, usize);
-type Map = HashMap<Key, EnginePart>;
+use grid::{Grid, GridError, Point};
 
+type Map = Grid<Option<EnginePart>>;
+
+/// A number in the schematic, spanning columns `start..=end` of `row`
 #[derive(Debug)]
-enum EnginePart {
-    Number(i32),
-    Symbol,
-    Gear,
+struct Number {
+    value: i64,
+    row: usize,
+    start: usize,
+    end: usize,
 }
 
-fn insert_number(key: Key, number: i32, map: &mut Map) {
-    for x in 0..number.checked_ilog10().unwrap_or(0) + 1 {
-        map.insert((key.0, key.1 - x as usize), EnginePart::Number(number));
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at ({}, {}..={})",
+            self.value, self.row, self.start, self.end
+        )
     }
 }
 
-fn look_around(key: &Key, map: &Map) -> Option<Vec<i32>> {
-    let (row, col) = *key;
-    let mut result = vec![];
+#[derive(Debug)]
+enum SchematicError {
+    Grid(GridError),
+    /// The number starting at this point doesn't fit in an `i64`
+    NumberOverflow(Point),
+}
 
-    // Search left and right first
-    for x in [0, 2] {
-        if let Some(EnginePart::Number(num)) = map.get(&(row, col + x - 1)) {
-            result.push(*num);
+impl fmt::Display for SchematicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchematicError::Grid(err) => write!(f, "Invalid schematic: {}", err),
+            SchematicError::NumberOverflow(at) => {
+                write!(f, "Invalid schematic: the number at {:?} overflows", at)
+            }
         }
     }
+}
 
-    // Avoid duplications by then searching top, top-left, top-right, bottom, bottom-left and bottom-right
-    'row: for y in [0, 2] {
-        for x in [1, 0, 2] {
-            if let Some(EnginePart::Number(num)) = map.get(&(row + y - 1, col + x - 1)) {
-                result.push(*num);
-                if x == 1 {
-                    // break, due to corners being the same number (if any)
-                    continue 'row;
-                }
-            }
+#[derive(Debug, Clone)]
+enum EnginePart {
+    /// Id of the number covering this cell, an index into the list of numbers
+    Number(usize),
+    Symbol(char),
+}
+
+/// A symbol and the numbers touching it
+#[derive(Debug)]
+struct SymbolAdjacency<'a> {
+    symbol: char,
+    at: Point,
+    numbers: Vec<&'a Number>,
+}
+
+impl fmt::Display for SymbolAdjacency<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "'{}' at {:?} touches {} number(s)",
+            self.symbol,
+            self.at,
+            self.numbers.len()
+        )?;
+        for number in &self.numbers {
+            write!(f, "\n  {}", number)?;
         }
+        Ok(())
     }
+}
 
-    if result.is_empty() {
-        return None;
+impl SymbolAdjacency<'_> {
+    /// Product of the numbers touching the symbol, `None` if it doesn't fit in an `i64`
+    fn ratio(&self) -> Option<i64> {
+        self.numbers
+            .iter()
+            .try_fold(1i64, |ratio, number| ratio.checked_mul(number.value))
     }
-    Some(result)
 }
 
-fn update_number(number: &mut i32, digit: char) {
-    *number *= 10;
-    *number += digit.to_digit(10).unwrap() as i32;
+struct Schematic {
+    text: Grid<char>,
+    map: Map,
+    numbers: Vec<Number>,
 }
 
-fn parse_engine_schematic(data: Vec<String>) -> (i32, i32) {
-    let mut map = Map::new();
+/// Insert the number written in `columns` of `row`, leading zeros included
+fn insert_number(
+    row: usize,
+    columns: Range<usize>,
+    digits: &[char],
+    map: &mut Map,
+    numbers: &mut Vec<Number>,
+) -> Result<(), SchematicError> {
+    let id = numbers.len();
+    let value = digits[columns.clone()]
+        .iter()
+        .try_fold(0i64, |number, digit| {
+            number
+                .checked_mul(10)?
+                .checked_add(digit.to_digit(10).unwrap() as i64)
+        })
+        .ok_or(SchematicError::NumberOverflow((row, columns.start)))?;
+    let number = Number {
+        value,
+        row,
+        start: columns.start,
+        end: columns.end - 1,
+    };
+    for x in number.start..=number.end {
+        map[(number.row, x)] = Some(EnginePart::Number(id));
+    }
+    numbers.push(number);
+    Ok(())
+}
 
-    // Insert symbols, if digit we collect and insert full number after a break (or we reach bounds)
-    for (y, row) in data.iter().enumerate() {
-        // let mut num = String::new();
-        let mut number = 0;
-        let x_bounds = row.len() - 1;
-        for (x, val) in row.char_indices() {
-            match val {
-                '.' => {
-                    if number != 0 {
-                        // end of number
-                        insert_number((y, x - 1), number, &mut map);
-                        number = 0
-                    }
-                }
-                n if n.is_ascii_digit() => {
-                    update_number(&mut number, n);
-                    if x == x_bounds {
-                        // digit is the last char in the row
-                        insert_number((y, x), number, &mut map);
-                        number = 0;
-                    }
-                }
-                c => {
-                    if number != 0 {
-                        insert_number((y, x - 1), number, &mut map);
-                        number = 0;
-                    }
-
-                    // insert symbol
-                    let symbol = match c {
-                        '*' => EnginePart::Gear,
-                        _ => EnginePart::Symbol,
-                    };
-                    map.insert((y, x), symbol);
-                }
+/// Ids of the numbers adjacent to `key`, every number only once however many cells it touches
+fn look_around(key: &Point, map: &Map) -> BTreeSet<usize> {
+    map.neighbors8(*key)
+        .filter_map(|point| match map[point] {
+            Some(EnginePart::Number(id)) => Some(id),
+            _ => None,
+        })
+        .collect()
+}
+
+fn parse_engine_schematic(data: Vec<String>) -> Result<Schematic, SchematicError> {
+    let schematic = Grid::from_lines(&data, |c| c).map_err(SchematicError::Grid)?;
+    let mut map = schematic.map(|_| None);
+    let mut numbers = vec![];
+
+    // Insert symbols, digits are collected from the start column of a number until the first
+    // non-digit (or the end of the row)
+    for (y, row) in schematic.rows().enumerate() {
+        let mut start = None;
+        for (x, &val) in row.iter().enumerate() {
+            if val.is_ascii_digit() {
+                start.get_or_insert(x);
+                continue;
+            }
+            if let Some(start) = start.take() {
+                insert_number(y, start..x, row, &mut map, &mut numbers)?;
             }
+            if val != '.' {
+                map[(y, x)] = Some(EnginePart::Symbol(val));
+            }
+        }
+        if let Some(start) = start {
+            // number runs until the end of the row
+            insert_number(y, start..row.len(), row, &mut map, &mut numbers)?;
         }
     }
 
-    // Search map for symbols, look for adjacent numbers
-    let mut sum = 0;
-    let mut gears = 0;
-    for (coord, engine_part) in map.iter() {
-        match engine_part {
-            EnginePart::Number(_) => (),
-            EnginePart::Gear => {
-                if let Some(numbers) = look_around(coord, &map) {
-                    sum += numbers.iter().sum::<i32>();
-                    if numbers.len() == 2 {
-                        gears += numbers[0] * numbers[1]
-                    }
-                }
-            }
-            EnginePart::Symbol => {
-                if let Some(numbers) = look_around(coord, &map) {
-                    sum += numbers.iter().sum::<i32>();
+    Ok(Schematic {
+        text: schematic,
+        map,
+        numbers,
+    })
+}
+
+impl Schematic {
+    /// Every symbol in reading order with the numbers touching it, only `symbol` if given
+    fn symbol_adjacency(&self, symbol: Option<char>) -> Vec<SymbolAdjacency<'_>> {
+        self.map
+            .iter()
+            .filter_map(|(at, engine_part)| match engine_part {
+                Some(EnginePart::Symbol(c)) if symbol.is_none_or(|symbol| symbol == *c) => {
+                    Some(SymbolAdjacency {
+                        symbol: *c,
+                        at,
+                        numbers: look_around(&at, &self.map)
+                            .into_iter()
+                            .map(|id| &self.numbers[id])
+                            .collect(),
+                    })
                 }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Ids of the numbers touching any symbol
+    fn part_number_ids(&self) -> BTreeSet<usize> {
+        self.map
+            .iter()
+            .filter(|(_, engine_part)| matches!(engine_part, Some(EnginePart::Symbol(_))))
+            .flat_map(|(at, _)| look_around(&at, &self.map))
+            .collect()
+    }
+
+    /// Sum of the numbers touching any symbol, a number touching several symbols counts once,
+    /// returns the start of the number at which the sum no longer fits in an `i64`
+    fn part_number_sum(&self) -> Result<i64, Point> {
+        self.part_number_ids().iter().try_fold(0i64, |sum, &id| {
+            let number = &self.numbers[id];
+            sum.checked_add(number.value)
+                .ok_or((number.row, number.start))
+        })
+    }
+
+    /// Every `gear` symbol touching exactly `neighbors` numbers
+    fn gears(&self, gear: char, neighbors: usize) -> Vec<SymbolAdjacency<'_>> {
+        self.symbol_adjacency(Some(gear))
+            .into_iter()
+            .filter(|adjacency| adjacency.numbers.len() == neighbors)
+            .collect()
+    }
+
+    /// Sum of the products of the numbers around every gear, returns the gear at which the sum no
+    /// longer fits in an `i64`
+    fn gear_ratio_sum(&self, gear: char, neighbors: usize) -> Result<i64, Point> {
+        self.gears(gear, neighbors)
+            .iter()
+            .try_fold(0i64, |sum, gear| {
+                gear.ratio()
+                    .and_then(|ratio| sum.checked_add(ratio))
+                    .ok_or(gear.at)
+            })
+    }
+
+    /// Reprint the schematic with part numbers in green, numbers touching no symbol in red and
+    /// gears in yellow, followed by the numbers of every gear
+    fn render(&self, gear: char, neighbors: usize) -> String {
+        const RESET: &str = "\x1b[0m";
+        const PART: &str = "\x1b[32m";
+        const NOT_PART: &str = "\x1b[31m";
+        const GEAR: &str = "\x1b[1;33m";
+        const DIM: &str = "\x1b[2m";
+
+        let part_numbers = self.part_number_ids();
+        let gears = self.gears(gear, neighbors);
+        let mut out = String::new();
+        for (row, line) in self.text.rows().enumerate() {
+            for (col, &c) in line.iter().enumerate() {
+                let color = match self.map[(row, col)] {
+                    Some(EnginePart::Number(id)) if part_numbers.contains(&id) => PART,
+                    Some(EnginePart::Number(_)) => NOT_PART,
+                    Some(EnginePart::Symbol(_)) if gears.iter().any(|g| g.at == (row, col)) => GEAR,
+                    Some(EnginePart::Symbol(_)) => "",
+                    None => DIM,
+                };
+                out.push_str(&format!("{}{}{}", color, c, RESET));
             }
+            out.push('\n');
+        }
+
+        let sum = self
+            .part_number_sum()
+            .map_or("overflow".to_string(), |sum| sum.to_string());
+        out.push_str(&format!(
+            "\n{} of {} number(s) are part numbers, sum {}\n",
+            part_numbers.len(),
+            self.numbers.len(),
+            sum
+        ));
+        out.push_str(&format!("{} gear(s)\n", gears.len()));
+        for gear in &gears {
+            let numbers: Vec<String> = gear.numbers.iter().map(|n| n.to_string()).collect();
+            let ratio = gear
+                .ratio()
+                .map_or("overflow".to_string(), |ratio| ratio.to_string());
+            out.push_str(&format!(
+                "'{}' at {:?}: {} = {}\n",
+                gear.symbol,
+                gear.at,
+                numbers.join(" * "),
+                ratio
+            ));
         }
+        out
     }
-    (sum, gears)
 }
 
 fn input() -> Vec<String> {
@@ -128,16 +285,225 @@ fn input() -> Vec<String> {
 fn main() {
     let time = Instant::now();
     let input = input();
-    let (part_total, gear_ratios) = parse_engine_schematic(input);
-    part1(&part_total);
-    part2(&gear_ratios);
+    let schematic = parse_engine_schematic(input).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    let args: Vec<String> = std::env::args().collect();
+    let value = |flag: &str| {
+        let i = args.iter().position(|arg| arg == flag)?;
+        Some(
+            args.get(i + 1)
+                .unwrap_or_else(|| panic!("{} expects a value", flag)),
+        )
+    };
+
+    // List the numbers touching every symbol of a type: --symbol <char>
+    if let Some(symbol) = value("--symbol") {
+        let symbol = symbol.chars().next();
+        let adjacency = schematic.symbol_adjacency(symbol);
+        for symbol in &adjacency {
+            println!("{}", symbol);
+        }
+        println!(
+            "{} symbol(s) touching {} number(s)",
+            adjacency.len(),
+            adjacency.iter().map(|a| a.numbers.len()).sum::<usize>()
+        );
+        return;
+    }
+
+    // Gears are '*' symbols touching exactly two numbers unless given:
+    // --gear <char> --gear-neighbors <n>
+    let gear = value("--gear").and_then(|gear| gear.chars().next());
+    let neighbors = value("--gear-neighbors").map(|n| n.parse().expect("Invalid neighbor count"));
+
+    let (gear, neighbors) = (gear.unwrap_or('*'), neighbors.unwrap_or(2));
+
+    // Reprint the schematic with part numbers, other numbers and gears colored: --render
+    if args.iter().any(|arg| arg == "--render") {
+        print!("{}", schematic.render(gear, neighbors));
+        return;
+    }
+
+    let part_number_sum = schematic.part_number_sum().unwrap_or_else(|at| {
+        eprintln!("Part number sum overflowed at the number at {:?}", at);
+        std::process::exit(1);
+    });
+    part1(&part_number_sum);
+    let gear_ratio_sum = schematic
+        .gear_ratio_sum(gear, neighbors)
+        .unwrap_or_else(|at| {
+            eprintln!("Gear ratio sum overflowed at the gear at {:?}", at);
+            std::process::exit(1);
+        });
+    part2(&gear_ratio_sum);
     println!("Time elapsed is {:?}", time.elapsed())
 }
 
-fn part1(result: &i32) {
+fn part1(result: &i64) {
     println!("Part 1 answer: {:?}", result);
 }
 
-fn part2(result: &i32) {
+fn part2(result: &i64) {
     println!("Part 2 answer: {:?}", result);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Schematic {
+        parse_engine_schematic(text.lines().map(String::from).collect()).unwrap()
+    }
+
+    #[test]
+    fn zeros_and_row_ends() {
+        let schematic = parse(include_str!("../example2.txt"));
+        let numbers: Vec<(i64, usize, usize, usize)> = schematic
+            .numbers
+            .iter()
+            .map(|n| (n.value, n.row, n.start, n.end))
+            .collect();
+        assert_eq!(
+            vec![
+                (0, 0, 0, 0),
+                (7, 0, 2, 4),
+                (12, 1, 6, 7),
+                (5, 3, 0, 0),
+                (9, 3, 7, 7),
+                (3, 5, 6, 6)
+            ],
+            numbers
+        );
+        assert_eq!(Ok(36), schematic.part_number_sum());
+        assert_eq!(Ok(27), schematic.gear_ratio_sum('*', 2));
+    }
+
+    #[test]
+    fn diagonal_neighbors() {
+        let schematic = parse(include_str!("../example2.txt"));
+        let adjacency: Vec<(char, Point, Vec<i64>)> = schematic
+            .symbol_adjacency(None)
+            .iter()
+            .map(|a| (a.symbol, a.at, a.numbers.iter().map(|n| n.value).collect()))
+            .collect();
+        assert_eq!(
+            vec![
+                ('*', (0, 1), vec![0, 7]),
+                ('#', (2, 5), vec![12]),
+                ('*', (4, 1), vec![5]),
+                ('*', (4, 6), vec![9, 3])
+            ],
+            adjacency
+        );
+    }
+
+    #[test]
+    fn identical_numbers_around_one_symbol() {
+        let schematic = parse("5.5\n.*.");
+        assert_eq!(2, schematic.numbers.len());
+        assert_eq!(Ok(10), schematic.part_number_sum());
+        assert_eq!(Ok(25), schematic.gear_ratio_sum('*', 2));
+    }
+
+    #[test]
+    fn ragged_schematic() {
+        assert!(matches!(
+            parse_engine_schematic(vec!["467..".into(), String::new(), "..*".into()]),
+            Err(SchematicError::Grid(GridError::Ragged {
+                row: 2,
+                expected: 5,
+                found: 3
+            }))
+        ));
+    }
+
+    #[test]
+    fn render_colors_and_summary() {
+        let render = parse(include_str!("../example2.txt")).render('*', 2);
+        let mut lines = render.lines();
+        let first = lines.next().unwrap();
+        // `0*007`: part numbers in green around a gear in yellow
+        assert!(
+            first.starts_with("\x1b[32m0\x1b[0m\x1b[1;33m*\x1b[0m\x1b[32m0\x1b[0m"),
+            "{:?}",
+            first
+        );
+        // A '*' touching a single number is not a gear and keeps its color
+        let row = render.lines().nth(4).unwrap();
+        assert!(row.starts_with("\x1b[2m.\x1b[0m*\x1b[0m"), "{:?}", row);
+        assert!(row.contains("\x1b[1;33m*\x1b[0m"), "{:?}", row);
+
+        let summary: Vec<&str> = render.lines().skip(7).collect();
+        assert_eq!(
+            vec![
+                "6 of 6 number(s) are part numbers, sum 36",
+                "2 gear(s)",
+                "'*' at (0, 1): 0 at (0, 0..=0) * 7 at (0, 2..=4) = 0",
+                "'*' at (4, 6): 9 at (3, 7..=7) * 3 at (5, 6..=6) = 27",
+            ],
+            summary
+        );
+
+        // `114` touches no symbol and is red
+        let render = parse(include_str!("../example.txt")).render('*', 2);
+        let first = render.lines().next().unwrap();
+        assert!(
+            first.ends_with(
+                "\x1b[31m1\x1b[0m\x1b[31m1\x1b[0m\x1b[31m4\x1b[0m\x1b[2m.\x1b[0m\x1b[2m.\x1b[0m"
+            ),
+            "{:?}",
+            first
+        );
+        assert!(render.contains("\n8 of 10 number(s) are part numbers, sum 4361\n"));
+    }
+
+    #[test]
+    fn large_gear_ratios() {
+        let schematic = parse("999.999\n...*...\n999.999");
+        assert_eq!(Ok(999i64.pow(4)), schematic.gear_ratio_sum('*', 4));
+
+        let schematic = parse("9999.9999\n9999*9999\n9999.9999");
+        assert_eq!(Err((1, 4)), schematic.gear_ratio_sum('*', 6));
+    }
+
+    #[test]
+    fn large_numbers() {
+        let schematic = parse("99999999999*\n");
+        assert_eq!(Ok(99_999_999_999), schematic.part_number_sum());
+
+        assert!(matches!(
+            parse_engine_schematic(vec!["*99999999999999999999".to_string()]),
+            Err(SchematicError::NumberOverflow((0, 1)))
+        ));
+
+        let schematic = parse("9223372036854775807\n*..................\n9223372036854775807");
+        assert_eq!(Err((2, 0)), schematic.part_number_sum());
+    }
+
+    #[test]
+    fn symbols_on_edges_and_corners() {
+        let schematic = parse(include_str!("../example3.txt"));
+        let adjacency: Vec<(char, Point, Vec<i64>)> = schematic
+            .symbol_adjacency(None)
+            .iter()
+            .map(|a| (a.symbol, a.at, a.numbers.iter().map(|n| n.value).collect()))
+            .collect();
+        assert_eq!(
+            vec![
+                ('*', (0, 0), vec![1, 3]),
+                ('#', (0, 4), vec![9]),
+                ('*', (0, 7), vec![2, 4]),
+                ('%', (2, 0), vec![3, 5]),
+                ('&', (2, 7), vec![4, 6]),
+                ('+', (4, 0), vec![5, 7]),
+                ('=', (4, 3), vec![8]),
+                ('-', (4, 7), vec![6])
+            ],
+            adjacency
+        );
+        assert_eq!(Ok(45), schematic.part_number_sum());
+        assert_eq!(Ok(11), schematic.gear_ratio_sum('*', 2));
+    }
+}
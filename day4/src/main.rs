@@ -1,4 +1,4 @@
-use std::{io::BufRead, time::Instant};
+use std::{fmt, io::BufRead, ops::Range, str::FromStr, time::Instant};
 
 use itertools::Itertools;
 #[derive(Debug)]
@@ -7,27 +7,28 @@ struct Card {
     actual: Vec<i32>,
 }
 
+fn parse_card(line: &str) -> Card {
+    let (_, parts) = line.split_once(':').unwrap();
+    let (winning, actual) = parts
+        .trim()
+        .split('|')
+        .map(|batch| {
+            batch
+                .split_whitespace()
+                .filter_map(|n| n.parse().ok())
+                .collect_vec()
+        })
+        .collect_tuple::<(_, _)>()
+        .unwrap();
+
+    Card { winning, actual }
+}
+
 fn input() -> Vec<Card> {
     std::io::stdin()
         .lock()
         .lines()
-        .map(|line| line.unwrap())
-        .map(|line| {
-            let (_, parts) = line.split_once(':').unwrap();
-            let (winning, actual) = parts
-                .trim()
-                .split('|')
-                .map(|batch| {
-                    batch
-                        .split_whitespace()
-                        .filter_map(|n| n.parse().ok())
-                        .collect_vec()
-                })
-                .collect_tuple::<(_, _)>()
-                .unwrap();
-
-            Card { winning, actual }
-        })
+        .map(|line| parse_card(&line.unwrap()))
         .collect()
 }
 
@@ -38,36 +39,308 @@ fn get_match_count(card: &Card) -> usize {
         .count()
 }
 
+/// How the matches of a card turn into points
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scoring {
+    /// 1 point for the first match, doubled for every other one
+    Doubling,
+    /// 1 point per match
+    Linear,
+    /// 1, 2, 3, 5, 8... points, every match adds the points of the two previous ones
+    Fibonacci,
+}
+
+impl Scoring {
+    /// Points of a card with `matches` winning numbers, a card without any is worth nothing.
+    /// `None` if the points don't fit in a `u64`.
+    fn score(self, matches: usize) -> Option<u64> {
+        if matches == 0 {
+            return Some(0);
+        }
+        match self {
+            Scoring::Doubling => 1u64.checked_shl(u32::try_from(matches - 1).ok()?),
+            Scoring::Linear => u64::try_from(matches).ok(),
+            Scoring::Fibonacci => (1..matches)
+                .try_fold((1u64, 1u64), |(prev, cur), _| {
+                    Some((cur, prev.checked_add(cur)?))
+                })
+                .map(|(_, cur)| cur),
+        }
+    }
+}
+
+impl FromStr for Scoring {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "doubling" => Ok(Scoring::Doubling),
+            "linear" => Ok(Scoring::Linear),
+            "fibonacci" => Ok(Scoring::Fibonacci),
+            _ => Err(format!("Unknown scoring '{}'", s)),
+        }
+    }
+}
+
 fn main() {
     let time = Instant::now();
+
+    // Score part 1 with another rule: --scoring <doubling|linear|fibonacci>
+    let args: Vec<String> = std::env::args().collect();
+    let scoring = args
+        .iter()
+        .position(|arg| arg == "--scoring")
+        .map(|i| {
+            args.get(i + 1)
+                .expect("--scoring expects a rule")
+                .parse()
+                .unwrap()
+        })
+        .unwrap_or(Scoring::Doubling);
+
+    // Handle copies won past the last card in part 2: --past-end <ignore|error>
+    let policy = args
+        .iter()
+        .position(|arg| arg == "--past-end")
+        .map(|i| {
+            args.get(i + 1)
+                .expect("--past-end expects a policy")
+                .parse()
+                .unwrap()
+        })
+        .unwrap_or(PastEndPolicy::Ignore);
+
     let input = input();
-    part1(&input);
-    part2(&input);
+
+    // List the cards that win copies past the last card: --validate
+    let problems = validate(&input);
+    if args.iter().any(|arg| arg == "--validate") {
+        for problem in &problems {
+            println!("{}", problem);
+        }
+        if problems.is_empty() {
+            println!("No problems found");
+        }
+        return;
+    }
+    if policy == PastEndPolicy::Ignore {
+        for problem in &problems {
+            eprintln!("Warning: {}", problem);
+        }
+    }
+
+    part1(&input, scoring);
+    part2(&input, policy);
     println!("Time elapsed is {:?}", time.elapsed())
 }
 
-fn part1(input: &[Card]) {
-    let result: i32 = input
-        .iter()
-        .map(|card| {
-            let exp = get_match_count(card) - 1;
-            i32::pow(2, exp as u32)
-        })
-        .sum();
+/// Total points of all cards, fails with the (1-based) card at which they no longer fit in a `u64`
+fn total_points(input: &[Card], scoring: Scoring) -> Result<u64, CountError> {
+    input.iter().enumerate().try_fold(0u64, |total, (i, card)| {
+        scoring
+            .score(get_match_count(card))
+            .and_then(|points| total.checked_add(points))
+            .ok_or(CountError::Overflow(i + 1))
+    })
+}
+
+fn part1(input: &[Card], scoring: Scoring) {
+    let result = total_points(input, scoring).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
     println!("Part 1 answer: {:?}", result);
 }
 
-fn part2(input: &[Card]) {
-    let mut card_count: Vec<usize> = vec![1; input.len()];
-    input.iter().enumerate().for_each(|(i, card)| {
-        let match_count = get_match_count(card);
+/// What to do with won copies of cards past the last one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PastEndPolicy {
+    /// Drop the copies that point past the last card
+    Ignore,
+    /// Stop with the card that won them
+    Error,
+}
+
+impl FromStr for PastEndPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(PastEndPolicy::Ignore),
+            "error" => Ok(PastEndPolicy::Error),
+            _ => Err(format!("Unknown policy '{}'", s)),
+        }
+    }
+}
+
+/// A card whose matches win copies past the last card, `card` is 1-based
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PastEnd {
+    card: usize,
+    missing: usize,
+}
+
+impl fmt::Display for PastEnd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Card {} wins {} copies past the last card",
+            self.card, self.missing
+        )
+    }
+}
+
+/// Why the cards could not be counted, the cards are 1-based
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CountError {
+    Overflow(usize),
+    PastEnd(PastEnd),
+}
+
+impl fmt::Display for CountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CountError::Overflow(card) => write!(f, "Total overflowed at card {}", card),
+            CountError::PastEnd(past_end) => write!(f, "{}", past_end),
+        }
+    }
+}
+
+/// Cards that win copies of cards that don't exist, in order
+fn validate(input: &[Card]) -> Vec<PastEnd> {
+    (0..input.len())
+        .filter_map(|i| won_cards(input, i).1)
+        .collect()
+}
+
+/// Indices of the cards won by card `i` that exist, and the copies it wins past the last card
+fn won_cards(input: &[Card], i: usize) -> (Range<usize>, Option<PastEnd>) {
+    let end = i + 1 + get_match_count(&input[i]);
+    let past_end = (end > input.len()).then(|| PastEnd {
+        card: i + 1,
+        missing: end - input.len(),
+    });
+    (i + 1..end.min(input.len()), past_end)
+}
+
+/// Total number of scratchcards once every won copy is processed. Copies of a card all win the
+/// same cards, so the copies are added to each following card at once instead of one by one.
+/// Copies past the last card are dropped or reported depending on `policy`.
+fn count_cards(input: &[Card], policy: PastEndPolicy) -> Result<u128, CountError> {
+    let mut card_count: Vec<u128> = vec![1; input.len()];
+    for i in 0..input.len() {
+        let (won, past_end) = won_cards(input, i);
+        if let Some(past_end) = past_end.filter(|_| policy == PastEndPolicy::Error) {
+            return Err(CountError::PastEnd(past_end));
+        }
         let current_copies = card_count[i];
-        for _ in 0..current_copies {
-            for j in 0..match_count {
-                card_count[i + j + 1] += 1;
-            }
+        for count in &mut card_count[won] {
+            *count = count
+                .checked_add(current_copies)
+                .ok_or(CountError::Overflow(i + 1))?;
         }
+    }
+    card_count
+        .iter()
+        .try_fold(0u128, |total, &count| total.checked_add(count))
+        .ok_or(CountError::Overflow(input.len()))
+}
+
+fn part2(input: &[Card], policy: PastEndPolicy) {
+    let result = count_cards(input, policy).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
     });
-    let result: usize = card_count.iter().sum();
     println!("Part 2 answer: {:?}", result);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doubling_score() {
+        assert_eq!(Some(0), Scoring::Doubling.score(0));
+        assert_eq!(Some(1), Scoring::Doubling.score(1));
+        assert_eq!(Some(8), Scoring::Doubling.score(4));
+        assert_eq!(Some(1 << 63), Scoring::Doubling.score(64));
+        assert_eq!(None, Scoring::Doubling.score(70));
+    }
+
+    #[test]
+    fn linear_score() {
+        assert_eq!(Some(0), Scoring::Linear.score(0));
+        assert_eq!(Some(1), Scoring::Linear.score(1));
+        assert_eq!(Some(4), Scoring::Linear.score(4));
+        assert_eq!(Some(70), Scoring::Linear.score(70));
+    }
+
+    #[test]
+    fn fibonacci_score() {
+        assert_eq!(Some(0), Scoring::Fibonacci.score(0));
+        assert_eq!(Some(1), Scoring::Fibonacci.score(1));
+        assert_eq!(Some(2), Scoring::Fibonacci.score(2));
+        assert_eq!(Some(5), Scoring::Fibonacci.score(4));
+        assert_eq!(Some(12200160415121876738), Scoring::Fibonacci.score(92));
+        assert_eq!(None, Scoring::Fibonacci.score(93));
+    }
+
+    #[test]
+    fn example_points() {
+        // Cards with 4, 1 and no matches
+        let input = parse(include_str!("../example2.txt"));
+        assert_eq!(Ok(9), total_points(&input, Scoring::Doubling));
+        assert_eq!(Ok(5), total_points(&input, Scoring::Linear));
+        assert_eq!(Ok(6), total_points(&input, Scoring::Fibonacci));
+    }
+
+    #[test]
+    fn total_points_overflow() {
+        let card = |matches: i32| Card {
+            winning: (1..=matches).collect(),
+            actual: (1..=matches).collect(),
+        };
+        let input = [card(0), card(1), card(70)];
+        assert_eq!(
+            Err(CountError::Overflow(3)),
+            total_points(&input, Scoring::Doubling)
+        );
+        assert_eq!(Ok(71), total_points(&input, Scoring::Linear));
+    }
+
+    fn parse(text: &str) -> Vec<Card> {
+        text.lines().map(parse_card).collect()
+    }
+
+    #[test]
+    fn copies_past_the_end() {
+        let input = parse(include_str!("../example3.txt"));
+        assert_eq!(Ok(4), count_cards(&input, PastEndPolicy::Ignore));
+        assert_eq!(
+            Err(CountError::PastEnd(PastEnd {
+                card: 3,
+                missing: 2
+            })),
+            count_cards(&input, PastEndPolicy::Error)
+        );
+    }
+
+    #[test]
+    fn example_within_bounds() {
+        let input = parse(include_str!("../example.txt"));
+        assert_eq!(Ok(30), count_cards(&input, PastEndPolicy::Error));
+        assert_eq!(Vec::<PastEnd>::new(), validate(&input));
+    }
+
+    #[test]
+    fn validator_warnings() {
+        let input = parse(include_str!("../example3.txt"));
+        assert_eq!(
+            vec![PastEnd {
+                card: 3,
+                missing: 2
+            }],
+            validate(&input)
+        );
+    }
+}
@@ -14,27 +14,140 @@ fn input() -> Vec<Vec<i64>> {
         .collect()
 }
 
+/// Build the difference rows of a history, down to the first row of only zeros
+fn difference_rows(seq: &[i64]) -> Vec<Vec<i64>> {
+    let mut rows = vec![seq.to_vec()];
+    while let Some(current_seq) = rows.last().filter(|row| row.iter().any(|&n| n != 0)) {
+        let next_seq = current_seq
+            .windows(2)
+            .map(|w| w[1] - w[0])
+            .collect::<Vec<i64>>();
+        rows.push(next_seq);
+    }
+    rows
+}
+
 fn solve(input: &Vec<Vec<i64>>) -> i64 {
     let mut result = 0;
     for seq in input {
-        let mut current_seq = seq.clone();
-        let mut last: Vec<i64> = Vec::new();
-
-        while current_seq.iter().any(|&n| n != 0) {
-            last.push(*current_seq.last().unwrap());
-            current_seq = current_seq
-                .windows(2)
-                .map(|w| w[1] - w[0])
-                .collect::<Vec<i64>>();
-        }
-        result += last.iter().rev().sum::<i64>();
+        result += difference_rows(seq)
+            .iter()
+            .filter_map(|row| row.last())
+            .sum::<i64>();
     }
     result
 }
 
+/// The difference pyramid of a single history, with the extrapolated values on both ends
+struct Pyramid {
+    rows: Vec<Vec<i64>>,
+    previous: Vec<i64>,
+    next: Vec<i64>,
+}
+
+impl Pyramid {
+    fn new(seq: &[i64]) -> Self {
+        let rows = difference_rows(seq);
+        let mut previous = vec![0; rows.len()];
+        let mut next = vec![0; rows.len()];
+
+        // Extrapolate bottom-up, the zero row extends with zeros on both sides
+        for (i, row) in rows.iter().enumerate().rev().skip(1) {
+            previous[i] = row.first().unwrap_or(&0) - previous[i + 1];
+            next[i] = row.last().unwrap_or(&0) + next[i + 1];
+        }
+
+        Pyramid {
+            rows,
+            previous,
+            next,
+        }
+    }
+
+    /// Each row with its extrapolated values, as (value, extrapolated) cells
+    fn cells(&self) -> impl Iterator<Item = Vec<(i64, bool)>> + '_ {
+        self.rows.iter().enumerate().map(|(i, row)| {
+            std::iter::once((self.previous[i], true))
+                .chain(row.iter().map(|&n| (n, false)))
+                .chain(std::iter::once((self.next[i], true)))
+                .collect()
+        })
+    }
+
+    /// Render the pyramid like the puzzle statement, extrapolated values are wrapped in brackets
+    fn render_text(&self) -> String {
+        let format_cell = |&(n, extrapolated): &(i64, bool)| match extrapolated {
+            true => format!("[{}]", n),
+            false => n.to_string(),
+        };
+        let width = self
+            .cells()
+            .flatten()
+            .map(|cell| format_cell(&cell).len())
+            .max()
+            .unwrap_or(0)
+            + 2;
+        // Every row is shifted by half a cell compared to the one above it
+        let indent = width.div_ceil(2);
+
+        let mut out = String::new();
+        for (i, row) in self.cells().enumerate() {
+            let line = row
+                .iter()
+                .map(|cell| format!("{:>width$}", format_cell(cell)))
+                .collect::<String>();
+            out.push_str(&" ".repeat(i * indent));
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Render the pyramid as a Markdown table, extrapolated values are bold
+    fn render_markdown(&self) -> String {
+        // Values of a row sit in every other column, offset by the depth of the row
+        let columns = self.rows[0].len() * 2 + 3;
+
+        let mut out = format!("| depth |{}\n", " |".repeat(columns));
+        out.push_str(&format!("| ---: |{}\n", " :---: |".repeat(columns)));
+        for (i, row) in self.cells().enumerate() {
+            let mut line = vec![String::new(); columns];
+            for (j, (n, extrapolated)) in row.into_iter().enumerate() {
+                line[i + j * 2] = match extrapolated {
+                    true => format!("**{}**", n),
+                    false => n.to_string(),
+                };
+            }
+            out.push_str(&format!("| {} | {} |\n", i, line.join(" | ")));
+        }
+        out
+    }
+}
+
 fn main() {
     let time = Instant::now();
     let mut input = input();
+
+    // Print the difference pyramid of a single history: --pyramid <line> [--markdown]
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(line) = args.iter().position(|arg| arg == "--pyramid") {
+        let line: usize = args
+            .get(line + 1)
+            .and_then(|n| n.parse().ok())
+            .expect("--pyramid expects a line number");
+        let history = line
+            .checked_sub(1)
+            .and_then(|i| input.get(i))
+            .expect("History line out of range");
+        let pyramid = Pyramid::new(history);
+        if args.iter().any(|arg| arg == "--markdown") {
+            print!("{}", pyramid.render_markdown());
+        } else {
+            print!("{}", pyramid.render_text());
+        }
+        return;
+    }
+
     part1(solve(&input));
     input.iter_mut().for_each(|h| h.reverse());
     part2(solve(&input));
@@ -49,3 +162,46 @@ fn part1(result: i64) {
 fn part2(result: i64) {
     println!("Part 2 answer: {:?}", result);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HISTORY: [i64; 6] = [10, 13, 16, 21, 30, 45];
+
+    #[test]
+    fn pyramid_extrapolates_like_solve() {
+        let pyramid = Pyramid::new(&HISTORY);
+        assert_eq!(5, pyramid.rows.len());
+        assert_eq!(vec![5, 5, -2, 2, 0], pyramid.previous);
+        assert_eq!(vec![68, 23, 8, 2, 0], pyramid.next);
+
+        let mut input = vec![HISTORY.to_vec()];
+        assert_eq!(pyramid.next[0], solve(&input));
+        input[0].reverse();
+        assert_eq!(pyramid.previous[0], solve(&input));
+    }
+
+    #[test]
+    fn pyramid_renderers() {
+        let pyramid = Pyramid::new(&HISTORY);
+
+        let text = pyramid.render_text();
+        assert_eq!(5, text.lines().count());
+        assert_eq!(
+            "   [5]    10    13    16    21    30    45  [68]",
+            text.lines().next().unwrap()
+        );
+        assert_eq!(
+            "               [0]     0     0   [0]",
+            text.lines().last().unwrap()
+        );
+
+        // A header and a separator row, then one row per depth
+        let markdown = pyramid.render_markdown();
+        assert_eq!(7, markdown.lines().count());
+        let first = markdown.lines().nth(2).unwrap();
+        assert!(first.starts_with("| 0 | **5** |  | 10 |"), "{}", first);
+        assert!(first.ends_with("| 45 |  | **68** |"), "{}", first);
+    }
+}
@@ -0,0 +1,291 @@
+//! A dense 2D grid shared by the puzzles that work on character maps.
+//!
+//! Positions are `(row, col)` tuples, matching the convention used by the days themselves.
+
+use std::{
+    fmt,
+    ops::{Index, IndexMut},
+};
+
+pub type Point = (usize, usize);
+
+/// Row and column offsets of the 4-connected neighbors: up, down, left, right
+pub const NEIGHBORS_4: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+/// Row and column offsets of the 8-connected neighbors, row by row starting top-left
+pub const NEIGHBORS_8: [(isize, isize); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GridError {
+    /// A row whose length differs from the first row, `row` is the index of the line in the
+    /// input, blank lines included
+    Ragged {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GridError::Ragged {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row {} has {} columns, expected {}",
+                row, found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GridError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T> Grid<T> {
+    pub fn new(width: usize, height: usize, fill: T) -> Self
+    where
+        T: Clone,
+    {
+        Grid {
+            width,
+            height,
+            cells: vec![fill; width * height],
+        }
+    }
+
+    /// Parse a grid from lines of text, mapping every character with `f`.
+    /// Empty lines are skipped, all other rows must have the same length as the first one.
+    pub fn from_lines<I, S>(lines: I, mut f: impl FnMut(char) -> T) -> Result<Self, GridError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut width = None;
+        let mut height = 0;
+        let mut cells = Vec::new();
+        for (index, line) in lines.into_iter().enumerate() {
+            let line = line.as_ref();
+            if line.is_empty() {
+                continue;
+            }
+
+            let before = cells.len();
+            cells.extend(line.chars().map(&mut f));
+            let found = cells.len() - before;
+            let expected = *width.get_or_insert(found);
+            if found != expected {
+                return Err(GridError::Ragged {
+                    row: index,
+                    expected,
+                    found,
+                });
+            }
+            height += 1;
+        }
+
+        Ok(Grid {
+            width: width.unwrap_or(0),
+            height,
+            cells,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn contains(&self, (row, col): Point) -> bool {
+        row < self.height && col < self.width
+    }
+
+    pub fn get(&self, point: Point) -> Option<&T> {
+        self.contains(point)
+            .then(|| &self.cells[point.0 * self.width + point.1])
+    }
+
+    pub fn get_mut(&mut self, point: Point) -> Option<&mut T> {
+        if self.contains(point) {
+            Some(&mut self.cells[point.0 * self.width + point.1])
+        } else {
+            None
+        }
+    }
+
+    /// Move `point` by a signed `(row, col)` offset, `None` if that leaves the grid
+    pub fn offset(&self, (row, col): Point, (dy, dx): (isize, isize)) -> Option<Point> {
+        let point = (row.checked_add_signed(dy)?, col.checked_add_signed(dx)?);
+        self.contains(point).then_some(point)
+    }
+
+    /// The in-bounds up, down, left and right neighbors of `point`
+    pub fn neighbors4(&self, point: Point) -> impl Iterator<Item = Point> + '_ {
+        NEIGHBORS_4
+            .iter()
+            .filter_map(move |&delta| self.offset(point, delta))
+    }
+
+    /// The in-bounds neighbors of `point` including the diagonals
+    pub fn neighbors8(&self, point: Point) -> impl Iterator<Item = Point> + '_ {
+        NEIGHBORS_8
+            .iter()
+            .filter_map(move |&delta| self.offset(point, delta))
+    }
+
+    pub fn row(&self, row: usize) -> &[T] {
+        &self.cells[row * self.width..(row + 1) * self.width]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> + '_ {
+        // `chunks` panics on a zero chunk size, an empty grid simply has no rows
+        self.cells.chunks(self.width.max(1))
+    }
+
+    pub fn column(&self, col: usize) -> impl Iterator<Item = &T> + '_ {
+        assert!(col < self.width, "Column {} out of bounds", col);
+        self.cells.iter().skip(col).step_by(self.width)
+    }
+
+    pub fn columns(&self) -> impl Iterator<Item = impl Iterator<Item = &T> + '_> + '_ {
+        (0..self.width).map(|col| self.column(col))
+    }
+
+    /// All positions, row by row
+    pub fn points(&self) -> impl Iterator<Item = Point> {
+        let width = self.width;
+        (0..self.height).flat_map(move |row| (0..width).map(move |col| (row, col)))
+    }
+
+    /// All cells with their position, row by row
+    pub fn iter(&self) -> impl Iterator<Item = (Point, &T)> + '_ {
+        self.points().zip(self.cells.iter())
+    }
+
+    /// Position of the first cell matching `predicate`, row by row
+    pub fn position(&self, predicate: impl Fn(&T) -> bool) -> Option<Point> {
+        self.cells
+            .iter()
+            .position(predicate)
+            .map(|i| (i / self.width, i % self.width))
+    }
+
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+}
+
+impl<T> Index<Point> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, point: Point) -> &T {
+        self.get(point).expect("Point out of bounds")
+    }
+}
+
+impl<T> IndexMut<Point> for Grid<T> {
+    fn index_mut(&mut self, point: Point) -> &mut T {
+        self.get_mut(point).expect("Point out of bounds")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Grid<char> {
+        Grid::from_lines(["abc", "def", "ghi"], |c| c).unwrap()
+    }
+
+    #[test]
+    fn from_lines_parsing() {
+        let grid = Grid::from_lines(["ab", "", "cd", ""], |c| c).unwrap();
+        assert_eq!(2, grid.width());
+        assert_eq!(2, grid.height());
+        assert_eq!('c', grid[(1, 0)]);
+    }
+
+    #[test]
+    fn from_lines_ragged() {
+        let err = Grid::from_lines(["abc", "", "de"], |c| c).unwrap_err();
+        assert_eq!(
+            GridError::Ragged {
+                row: 2,
+                expected: 3,
+                found: 2
+            },
+            err
+        );
+    }
+
+    #[test]
+    fn offset_at_edges() {
+        let grid = sample();
+        assert_eq!(None, grid.offset((0, 1), (-1, 0)));
+        assert_eq!(None, grid.offset((2, 1), (1, 0)));
+        assert_eq!(None, grid.offset((1, 0), (0, -1)));
+        assert_eq!(None, grid.offset((1, 2), (0, 1)));
+        assert_eq!(Some((0, 1)), grid.offset((1, 1), (-1, 0)));
+        assert_eq!(Some((2, 2)), grid.offset((1, 1), (1, 1)));
+    }
+
+    #[test]
+    fn neighbor_counts() {
+        let grid = sample();
+        for corner in [(0, 0), (0, 2), (2, 0), (2, 2)] {
+            assert_eq!(2, grid.neighbors4(corner).count());
+            assert_eq!(3, grid.neighbors8(corner).count());
+        }
+        assert_eq!(3, grid.neighbors4((0, 1)).count());
+        assert_eq!(5, grid.neighbors8((0, 1)).count());
+        assert_eq!(4, grid.neighbors4((1, 1)).count());
+        assert_eq!(8, grid.neighbors8((1, 1)).count());
+    }
+
+    #[test]
+    fn rows_and_columns() {
+        let grid = sample();
+        let rows: Vec<String> = grid.rows().map(|row| row.iter().collect()).collect();
+        assert_eq!(vec!["abc", "def", "ghi"], rows);
+        let columns: Vec<String> = grid.columns().map(|column| column.collect()).collect();
+        assert_eq!(vec!["adg", "beh", "cfi"], columns);
+    }
+
+    #[test]
+    #[should_panic]
+    fn column_out_of_bounds() {
+        let _ = sample().column(3);
+    }
+
+    #[test]
+    fn position_finding() {
+        let grid = sample();
+        assert_eq!(Some((1, 2)), grid.position(|&c| c == 'f'));
+        assert_eq!(None, grid.position(|&c| c == 'z'));
+    }
+}